@@ -1,17 +1,12 @@
-use crossterm::event::{KeyCode, KeyModifiers};
-
 use nu_engine::{maybe_print_errors, run_block, script::run_script_standalone, EvaluationContext};
 
 #[allow(unused_imports)]
 pub(crate) use nu_engine::script::{process_script, LineResult};
 
-use nu_ansi_term::{Color, Style};
+use crate::prompt::NuPrompt;
+use crate::session::EditorSession;
 
-use reedline::{
-    default_emacs_keybindings, DefaultCompleter, DefaultCompletionActionHandler,
-    DefaultHighlighter, DefaultHinter, DefaultPrompt, EditCommand, FileBackedHistory, Reedline,
-    Signal,
-};
+use reedline::Signal;
 
 #[allow(unused_imports)]
 use nu_data::config;
@@ -85,22 +80,18 @@ pub fn cli(
     context: EvaluationContext,
     options: super::app::CliOptions,
 ) -> Result<(), Box<dyn Error>> {
-    if let Some(cfg) = options.config {
+    if let Some(cfg) = &options.config {
         load_cfg_as_global_cfg(&context, PathBuf::from(cfg));
     } else {
         load_global_cfg(&context);
     }
 
-    let (skip_welcome_message, _prompt) = if let Some(cfg) = &context.configs().lock().global_config
-    {
-        (
-            cfg.var("skip_welcome_message")
-                .map(|x| x.is_true())
-                .unwrap_or(false),
-            cfg.var("prompt"),
-        )
+    let skip_welcome_message = if let Some(cfg) = &context.configs().lock().global_config {
+        cfg.var("skip_welcome_message")
+            .map(|x| x.is_true())
+            .unwrap_or(false)
     } else {
-        (false, None)
+        false
     };
 
     //Check whether dir we start in contains local cfg file and if so load it.
@@ -124,44 +115,13 @@ pub fn cli(
         let _ = nu_ansi_term::enable_ansi_support();
     }
 
-    loop {
-        let mut keybindings = default_emacs_keybindings();
-        keybindings.add_binding(
-            KeyModifiers::ALT,
-            KeyCode::Char('m'),
-            vec![EditCommand::BackspaceWord],
-        );
+    let mut line_editor = EditorSession::new(&context, &options)?;
+    let mut prompt = NuPrompt::new();
 
-        let history = Box::new(FileBackedHistory::with_file(50, "history.txt".into())?);
-        let commands = vec![
-            "test".into(),
-            "clear".into(),
-            "exit".into(),
-            "history".into(),
-            "logout".into(),
-            "hello world".into(),
-            "hello world reedline".into(),
-            "this is the reedline crate".into(),
-        ];
-
-        let completer = Box::new(DefaultCompleter::new_with_wordlen(commands.clone(), 2));
-
-        let mut line_editor = Reedline::new()
-            .with_history(history)?
-            .with_edit_mode(reedline::EditMode::Emacs)
-            .with_keybindings(keybindings)
-            .with_highlighter(Box::new(DefaultHighlighter::new(commands)))
-            .with_completion_action_handler(Box::new(
-                DefaultCompletionActionHandler::default().with_completer(completer.clone()),
-            ))
-            .with_hinter(Box::new(
-                DefaultHinter::default()
-                    .with_completer(completer) // or .with_history()
-                    // .with_inside_line()
-                    .with_style(Style::new().italic().fg(Color::LightGray)),
-            ));
-
-        let prompt = DefaultPrompt::new(1);
+    loop {
+        // Pick up changes made to the config by the previous command
+        line_editor.reload_config(&context);
+        prompt.update(&context);
 
         let sig = line_editor.read_line(&prompt)?;
         match sig {
@@ -292,29 +252,13 @@ pub fn parse_and_eval(line: &str, ctx: &EvaluationContext) -> Result<String, She
     result?.collect_string(Tag::unknown()).map(|x| x.item)
 }
 
-#[allow(dead_code)]
-fn current_branch() -> String {
-    #[cfg(feature = "shadow-rs")]
-    {
-        Some(shadow_rs::branch())
-            .map(|x| x.trim().to_string())
-            .filter(|x| !x.is_empty())
-            .map(|x| format!("({})", x))
-            .unwrap_or_default()
-    }
-    #[cfg(not(feature = "shadow-rs"))]
-    {
-        "".to_string()
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use nu_engine::EvaluationContext;
 
     #[quickcheck]
     fn quickcheck_parse(data: String) -> bool {
-        let (tokens, err) = nu_parser::lex(&data, 0);
+        let (tokens, err) = nu_parser::lex(&data, 0, nu_parser::NewlineMode::Normal);
         let (lite_block, err2) = nu_parser::parse_block(tokens);
         if err.is_none() && err2.is_none() {
             let context = EvaluationContext::basic();
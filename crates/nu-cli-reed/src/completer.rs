@@ -0,0 +1,173 @@
+use nu_engine::EvaluationContext;
+use nu_parser::ParserScope;
+use nu_protocol::{SignatureRegistry, VariableRegistry};
+use reedline::{Completer, Span, Suggestion, SuggestionCompleter, SuggestionKind};
+
+/// Completes nushell commands, their flags, variables and external commands on `PATH`.
+///
+/// The completions come from `nu-completion`, which finds the word under the cursor with the
+/// nushell lexer and parser. It uses the live scope of the context, so commands
+/// defined with `def` and variables set with `let` during the session are completed as well.
+/// Internal commands carry their usage line as the description of the suggestion.
+#[derive(Clone)]
+pub struct NuCompleter {
+    context: EvaluationContext,
+    // Words shorter than this are not completed
+    min_word_len: usize,
+}
+
+impl NuCompleter {
+    pub fn new(context: EvaluationContext) -> NuCompleter {
+        NuCompleter {
+            context,
+            min_word_len: 0,
+        }
+    }
+
+    /// A builder that only completes words of at least `min_word_len` characters, useful for
+    /// hints that would otherwise show up for every single character typed
+    pub fn with_min_word_len(mut self, min_word_len: usize) -> NuCompleter {
+        self.min_word_len = min_word_len;
+        self
+    }
+
+    fn suggestion(
+        &self,
+        line: &str,
+        span: Span,
+        completion: nu_completion::Suggestion,
+    ) -> Suggestion {
+        let value = completion.replacement;
+        let mut suggestion = Suggestion::new(value.clone(), span);
+        if completion.display != value {
+            suggestion = suggestion.with_display(completion.display);
+        }
+
+        if value.starts_with('$') {
+            suggestion.with_kind(SuggestionKind::Variable)
+        } else if value.starts_with('-') {
+            suggestion.with_kind(SuggestionKind::Flag)
+        } else if let Some(command) = self.context.scope.get_command(value.trim_end()) {
+            suggestion
+                .with_kind(SuggestionKind::Command)
+                .with_description(command.usage())
+        } else if is_command_position(line, span.start) {
+            // Not a command in scope, so it's an external one
+            suggestion.with_kind(SuggestionKind::Command)
+        } else {
+            suggestion
+        }
+    }
+}
+
+impl SuggestionCompleter for NuCompleter {
+    fn suggest(&self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let context = CompletionContext(&self.context);
+        let (start, completions) = nu_completion::NuCompleter {}.complete(line, pos, &context);
+
+        if line[start..pos].chars().count() < self.min_word_len {
+            return vec![];
+        }
+
+        let span = Span::new(start, pos);
+        completions
+            .into_iter()
+            .map(|completion| self.suggestion(line, span, completion))
+            .collect()
+    }
+}
+
+impl Completer for NuCompleter {
+    fn complete(&self, line: &str, pos: usize) -> Vec<(Span, String)> {
+        self.suggest(line, pos)
+            .into_iter()
+            .map(|suggestion| (suggestion.span, suggestion.value))
+            .collect()
+    }
+}
+
+/// Whether a word starting at `start` is the first one of a pipeline element
+fn is_command_position(line: &str, start: usize) -> bool {
+    match line[..start]
+        .trim_end_matches(|c| c == ' ' || c == '\t')
+        .chars()
+        .last()
+    {
+        Some(c) => matches!(c, '|' | ';' | '\n' | '(' | '{'),
+        None => true,
+    }
+}
+
+/// What `nu-completion` needs to know about the session
+struct CompletionContext<'a>(&'a EvaluationContext);
+
+impl<'a> nu_completion::CompletionContext for CompletionContext<'a> {
+    fn signature_registry(&self) -> &dyn SignatureRegistry {
+        &self.0.scope
+    }
+
+    fn scope(&self) -> &dyn ParserScope {
+        &self.0.scope
+    }
+
+    fn source(&self) -> &EvaluationContext {
+        self.0
+    }
+
+    fn variable_registry(&self) -> &dyn VariableRegistry {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_command_position, NuCompleter};
+    use nu_command::create_default_context;
+    use reedline::{Span, SuggestionCompleter, SuggestionKind};
+
+    fn completer() -> NuCompleter {
+        NuCompleter::new(create_default_context(false).expect("default context"))
+    }
+
+    #[test]
+    fn completes_commands_with_their_usage() {
+        let suggestions = completer().suggest("ls | wher", 9);
+        let where_ = suggestions
+            .iter()
+            .find(|suggestion| suggestion.value.trim_end() == "where")
+            .expect("where is suggested");
+
+        assert_eq!(where_.span, Span::new(5, 9));
+        assert_eq!(where_.kind, Some(SuggestionKind::Command));
+        assert!(where_.description.is_some());
+    }
+
+    #[test]
+    fn completes_flags_and_variables() {
+        let flags = completer().suggest("ls --al", 7);
+        assert!(flags
+            .iter()
+            .any(|suggestion| suggestion.value.trim_end() == "--all"
+                && suggestion.span == Span::new(3, 7)));
+
+        let variables = completer().suggest("echo $n", 7);
+        assert!(variables.iter().any(|suggestion| suggestion.value == "$nu"
+            && suggestion.kind == Some(SuggestionKind::Variable)));
+    }
+
+    #[test]
+    fn finds_the_command_position() {
+        assert!(is_command_position("ls", 0));
+        assert!(is_command_position("ls | wh", 5));
+        assert!(is_command_position("cd ..; gi", 7));
+        assert!(!is_command_position("ls --al", 3));
+    }
+
+    #[test]
+    fn short_words_are_not_completed_below_the_minimum() {
+        let completer = completer().with_min_word_len(3);
+
+        assert!(completer.suggest("wh", 2).is_empty());
+        assert!(!completer.suggest("whe", 3).is_empty());
+    }
+}
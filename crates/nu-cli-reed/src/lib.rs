@@ -1,8 +1,20 @@
+#[cfg(test)]
+#[macro_use]
+extern crate quickcheck_macros;
+
 pub mod app;
 mod cli;
+mod completer;
+mod prompt;
+mod session;
+mod validator;
 pub use crate::cli::cli;
 
 pub use crate::app::App;
 pub use crate::cli::{parse_and_eval, register_plugins, run_script_file};
+pub use crate::completer::NuCompleter;
+pub use crate::prompt::NuPrompt;
+pub use crate::session::EditorSession;
+pub use crate::validator::NuValidator;
 
 pub use nu_command::create_default_context;
@@ -0,0 +1,156 @@
+mod git;
+
+pub use git::{GitSegment, GitStatus};
+
+use crate::cli::parse_and_eval;
+
+use nu_engine::EvaluationContext;
+use nu_source::Text;
+use reedline::{DefaultPrompt, Prompt, PromptEditMode, PromptHistorySearch};
+
+use std::borrow::Cow;
+use std::path::Path;
+
+/// The prompt configured with the `prompt` setting of the nushell config.
+///
+/// The setting is nushell code, like `prompt = "build-string (pwd) '> '"`, evaluated by
+/// [`NuPrompt::update`] before each `read_line`. The result replaces the whole prompt, so the
+/// emacs mode indicator is left out. Without a `prompt` setting, or when evaluating it fails,
+/// the [`DefaultPrompt`] is shown instead, with the [`GitStatus`] of the current directory in
+/// front of its indicator.
+pub struct NuPrompt {
+    default_prompt: DefaultPrompt,
+    git: GitSegment,
+    // The evaluated prompt, `None` falls back to the default prompt
+    prompt: Option<String>,
+    // The prompt source that failed to evaluate, its error is only reported once
+    failed_source: Option<String>,
+}
+
+impl Default for NuPrompt {
+    fn default() -> Self {
+        NuPrompt::new()
+    }
+}
+
+impl NuPrompt {
+    pub fn new() -> NuPrompt {
+        NuPrompt {
+            default_prompt: DefaultPrompt::new(1),
+            git: GitSegment::new(),
+            prompt: None,
+            failed_source: None,
+        }
+    }
+
+    /// Evaluate the `prompt` setting of the current config and check the current directory's
+    /// repository
+    pub fn update(&mut self, context: &EvaluationContext) {
+        self.git.update(Path::new(&context.shell_manager().path()));
+
+        let source = if let Some(cfg) = &context.configs().lock().global_config {
+            cfg.var("prompt").and_then(|prompt| prompt.as_string().ok())
+        } else {
+            None
+        };
+
+        self.evaluate(source, context);
+    }
+
+    fn evaluate(&mut self, source: Option<String>, context: &EvaluationContext) {
+        let source = match source {
+            Some(source) => source,
+            None => {
+                self.prompt = None;
+                return;
+            }
+        };
+
+        match parse_and_eval(&source, context) {
+            Ok(prompt) => {
+                self.prompt = Some(prompt);
+                self.failed_source = None;
+            }
+            Err(err) => {
+                self.prompt = None;
+                if self.failed_source.as_ref() != Some(&source) {
+                    context
+                        .host()
+                        .lock()
+                        .print_err(err, &Text::from(source.clone()));
+                    self.failed_source = Some(source);
+                }
+            }
+        }
+    }
+}
+
+impl Prompt for NuPrompt {
+    fn render_prompt(&self, screen_width: usize) -> Cow<str> {
+        match &self.prompt {
+            Some(prompt) => Cow::Borrowed(prompt),
+            None => Prompt::render_prompt(&self.default_prompt, screen_width),
+        }
+    }
+
+    fn render_prompt_indicator(&self, edit_mode: PromptEditMode) -> Cow<str> {
+        let indicator = match (&self.prompt, edit_mode) {
+            (Some(_), PromptEditMode::Default) | (Some(_), PromptEditMode::Emacs) => {
+                return Cow::Borrowed("")
+            }
+            (Some(_), edit_mode) => return self.default_prompt.render_prompt_indicator(edit_mode),
+            (None, edit_mode) => self.default_prompt.render_prompt_indicator(edit_mode),
+        };
+
+        match self.git.status() {
+            Some(git_status) => Cow::Owned(format!("{}{}", git_status, indicator)),
+            None => indicator,
+        }
+    }
+
+    fn render_prompt_multiline_indicator(&self) -> Cow<str> {
+        self.default_prompt.render_prompt_multiline_indicator()
+    }
+
+    fn render_prompt_history_search_indicator(
+        &self,
+        history_search: PromptHistorySearch,
+    ) -> Cow<str> {
+        self.default_prompt
+            .render_prompt_history_search_indicator(history_search)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NuPrompt;
+    use nu_engine::EvaluationContext;
+    use reedline::{Prompt, PromptEditMode, DEFAULT_PROMPT_INDICATOR};
+
+    #[test]
+    fn falls_back_to_the_default_prompt_without_a_setting() {
+        let context = EvaluationContext::basic();
+        let mut prompt = NuPrompt::new();
+
+        prompt.update(&context);
+
+        assert!(prompt
+            .render_prompt_indicator(PromptEditMode::Emacs)
+            .ends_with(DEFAULT_PROMPT_INDICATOR));
+    }
+
+    #[test]
+    fn falls_back_to_the_default_prompt_when_evaluation_fails() {
+        let context = EvaluationContext::basic();
+        let mut prompt = NuPrompt::new();
+
+        prompt.evaluate(Some("echo (".into()), &context);
+
+        assert_eq!(prompt.prompt, None);
+        assert_eq!(prompt.failed_source, Some("echo (".into()));
+        assert_eq!(
+            prompt.render_prompt_indicator(PromptEditMode::Emacs),
+            DEFAULT_PROMPT_INDICATOR
+        );
+    }
+}
@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long the dirty/ahead/behind state of an unchanged repository is trusted.
+///
+/// Edits to tracked files don't touch `.git`, so the state is asked for again after a while
+/// even when HEAD, the refs and the index look the same.
+const STATUS_TTL: Duration = Duration::from_secs(5);
+
+/// How many repositories the status is remembered for
+const MAX_CACHED_REPOS: usize = 16;
+
+/// The branch of a repository and how it differs from its working tree and upstream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitStatus {
+    /// The checked out branch, or the abbreviated commit of a detached HEAD
+    pub branch: String,
+    pub dirty: bool,
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+impl fmt::Display for GitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}", self.branch)?;
+        if self.dirty {
+            write!(f, "*")?;
+        }
+        if self.ahead > 0 {
+            write!(f, " ↑{}", self.ahead)?;
+        }
+        if self.behind > 0 {
+            write!(f, " ↓{}", self.behind)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Computes the [`GitStatus`] of the repository a directory belongs to, cached per repository.
+///
+/// The branch is read from `.git/HEAD`, and the refs, `packed-refs` and `config` decide
+/// whether the cached state is still valid. The dirty and ahead/behind state comes from
+/// `git status`, which runs on a thread of its own so a large repository doesn't hold up the
+/// prompt. Until it finished the last known state is shown, the prompt picks up the new one
+/// when it is painted again.
+#[derive(Default)]
+pub struct GitSegment {
+    // The repository of the directory passed to the last update
+    current: Option<(PathBuf, Head)>,
+    // Keyed by the root of the working tree, shared with the threads running `git status`
+    cache: Arc<Mutex<HashMap<PathBuf, CachedStatus>>>,
+}
+
+struct CachedStatus {
+    // The state of `.git` the last `git status` was started for
+    state: RepoState,
+    // `None` until `git status` finished, or if it failed
+    details: Option<StatusDetails>,
+    checked: Instant,
+    pending: bool,
+}
+
+/// What `git status` reports
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct StatusDetails {
+    dirty: bool,
+    ahead: usize,
+    behind: usize,
+}
+
+/// What changes in `.git` when the status may have changed
+#[derive(Debug, Clone, PartialEq)]
+struct RepoState {
+    head: Head,
+    commit: Option<String>,
+    upstream: Option<String>,
+    index_modified: Option<SystemTime>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Head {
+    Branch(String),
+    Detached(String),
+}
+
+impl GitSegment {
+    pub fn new() -> GitSegment {
+        GitSegment::default()
+    }
+
+    /// Look up the repository containing `dir`, and ask `git status` about it in the
+    /// background if it changed or was last asked too long ago
+    pub fn update(&mut self, dir: &Path) {
+        self.current = find_git_dir(dir).and_then(|(work_dir, git_dir)| {
+            let state = repo_state(&git_dir)?;
+            let head = state.head.clone();
+            self.refresh(&work_dir, state);
+            Some((work_dir, head))
+        });
+    }
+
+    /// The status of the repository found by the last [`GitSegment::update`], `None` outside
+    /// of a repository
+    pub fn status(&self) -> Option<GitStatus> {
+        let (work_dir, head) = self.current.as_ref()?;
+        let details = self
+            .cache
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(work_dir).and_then(|cached| cached.details))
+            .unwrap_or_default();
+
+        Some(GitStatus {
+            branch: match head {
+                Head::Branch(branch) => branch.clone(),
+                Head::Detached(commit) => commit.chars().take(7).collect(),
+            },
+            dirty: details.dirty,
+            ahead: details.ahead,
+            behind: details.behind,
+        })
+    }
+
+    fn refresh(&self, work_dir: &Path, state: RepoState) {
+        let mut cache = match self.cache.lock() {
+            Ok(cache) => cache,
+            Err(_) => return,
+        };
+        match cache.get_mut(work_dir) {
+            Some(cached) if cached.pending => return,
+            Some(cached) if cached.state == state && cached.checked.elapsed() < STATUS_TTL => {
+                return
+            }
+            Some(cached) => {
+                // The last known state is shown until `git status` finished
+                cached.state = state;
+                cached.pending = true;
+            }
+            None => {
+                if cache.len() >= MAX_CACHED_REPOS {
+                    evict_oldest(&mut cache);
+                }
+                cache.insert(
+                    work_dir.to_path_buf(),
+                    CachedStatus {
+                        state,
+                        details: None,
+                        checked: Instant::now(),
+                        pending: true,
+                    },
+                );
+            }
+        }
+
+        let cache = Arc::clone(&self.cache);
+        let work_dir = work_dir.to_path_buf();
+        thread::spawn(move || {
+            let details = query_status(&work_dir);
+            if let Ok(mut cache) = cache.lock() {
+                // Gone if the repository was evicted in the meantime
+                if let Some(cached) = cache.get_mut(&work_dir) {
+                    cached.details = details;
+                    cached.checked = Instant::now();
+                    cached.pending = false;
+                }
+            }
+        });
+    }
+}
+
+fn evict_oldest(cache: &mut HashMap<PathBuf, CachedStatus>) {
+    let oldest = cache
+        .iter()
+        .min_by_key(|(_, cached)| cached.checked)
+        .map(|(work_dir, _)| work_dir.clone());
+    if let Some(oldest) = oldest {
+        cache.remove(&oldest);
+    }
+}
+
+/// The branch, the commits of the branch and its upstream, and when the index last changed
+fn repo_state(git_dir: &Path) -> Option<RepoState> {
+    let common_dir = common_dir(git_dir);
+    let head = read_head(git_dir)?;
+    let (commit, upstream) = match &head {
+        Head::Branch(branch) => (
+            resolve_ref(&common_dir, &format!("refs/heads/{}", branch)),
+            upstream_ref(&common_dir, branch)
+                .and_then(|upstream| resolve_ref(&common_dir, &upstream)),
+        ),
+        Head::Detached(commit) => (Some(commit.clone()), None),
+    };
+
+    Some(RepoState {
+        head,
+        commit,
+        upstream,
+        index_modified: fs::metadata(git_dir.join("index"))
+            .and_then(|metadata| metadata.modified())
+            .ok(),
+    })
+}
+
+/// Find the working tree and git directory of the repository containing `dir`
+fn find_git_dir(dir: &Path) -> Option<(PathBuf, PathBuf)> {
+    for ancestor in dir.ancestors() {
+        let dot_git = ancestor.join(".git");
+        if dot_git.is_dir() {
+            return Some((ancestor.to_path_buf(), dot_git));
+        }
+
+        // Worktrees and submodules have a `.git` file pointing to their git directory
+        if let Ok(contents) = fs::read_to_string(&dot_git) {
+            if let Some(git_dir) = contents.trim_end().strip_prefix("gitdir: ") {
+                return Some((ancestor.to_path_buf(), ancestor.join(git_dir)));
+            }
+        }
+    }
+
+    None
+}
+
+/// The directory holding the refs, shared by all worktrees of a repository
+fn common_dir(git_dir: &Path) -> PathBuf {
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common_dir) => git_dir.join(common_dir.trim_end()),
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+fn read_head(git_dir: &Path) -> Option<Head> {
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim_end();
+
+    match head.strip_prefix("ref: ") {
+        Some(reference) => Some(Head::Branch(
+            reference
+                .strip_prefix("refs/heads/")
+                .unwrap_or(reference)
+                .to_string(),
+        )),
+        None => Some(Head::Detached(head.to_string())),
+    }
+}
+
+/// The commit a ref points to, either as a loose ref or from `packed-refs`
+fn resolve_ref(common_dir: &Path, reference: &str) -> Option<String> {
+    if let Ok(commit) = fs::read_to_string(common_dir.join(reference)) {
+        return Some(commit.trim_end().to_string());
+    }
+
+    let packed_refs = fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+    packed_refs
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+        .filter_map(|line| line.split_once(' '))
+        .find(|(_, name)| *name == reference)
+        .map(|(commit, _)| commit.to_string())
+}
+
+/// The upstream ref of a branch, from its `remote` and `merge` settings in `config`
+fn upstream_ref(common_dir: &Path, branch: &str) -> Option<String> {
+    let config = fs::read_to_string(common_dir.join("config")).ok()?;
+    let section = format!("[branch \"{}\"]", branch);
+
+    let mut in_section = false;
+    let mut remote = None;
+    let mut merge = None;
+    for line in config.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_section = line == section;
+        } else if let (true, Some((key, value))) = (in_section, line.split_once('=')) {
+            match key.trim().to_lowercase().as_str() {
+                "remote" => remote = Some(value.trim().to_string()),
+                "merge" => merge = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    let (remote, merge) = (remote?, merge?);
+    if remote == "." {
+        // Tracking a local branch
+        Some(merge)
+    } else {
+        let branch = merge.strip_prefix("refs/heads/").unwrap_or(&merge);
+        Some(format!("refs/remotes/{}/{}", remote, branch))
+    }
+}
+
+/// Ask `git status` whether tracked files changed and how far the branch is from its upstream
+fn query_status(work_dir: &Path) -> Option<StatusDetails> {
+    let output = Command::new("git")
+        .args([
+            "status",
+            "--porcelain=v2",
+            "--branch",
+            "--untracked-files=no",
+        ])
+        // Don't refresh the index, which would get in the way of git commands run at the same
+        // time
+        .env("GIT_OPTIONAL_LOCKS", "0")
+        .current_dir(work_dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(parse_status(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Read the output of `git status --porcelain=v2 --branch`: header lines start with `#`, the
+/// ahead/behind counts are in the `# branch.ab +<ahead> -<behind>` one, and every other line
+/// is a changed file
+fn parse_status(output: &str) -> StatusDetails {
+    let mut details = StatusDetails::default();
+    for line in output.lines() {
+        match line.strip_prefix("# branch.ab ") {
+            Some(counts) => {
+                for count in counts.split_whitespace() {
+                    if let Some(ahead) = count.strip_prefix('+') {
+                        details.ahead = ahead.parse().unwrap_or(0);
+                    } else if let Some(behind) = count.strip_prefix('-') {
+                        details.behind = behind.parse().unwrap_or(0);
+                    }
+                }
+            }
+            None => details.dirty |= !line.starts_with('#'),
+        }
+    }
+
+    details
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    struct TempRepo(TempDir);
+
+    impl TempRepo {
+        fn new() -> TempRepo {
+            let dir = tempfile::tempdir().expect("create temp dir");
+            fs::create_dir_all(dir.path().join(".git/refs/heads")).expect("create git dir");
+            TempRepo(dir)
+        }
+
+        fn path(&self) -> &Path {
+            self.0.path()
+        }
+
+        fn write(&self, file: &str, contents: &str) {
+            fs::write(self.path().join(".git").join(file), contents).expect("write git file");
+        }
+    }
+
+    #[test]
+    fn reads_the_branch_from_a_subdirectory() {
+        let repo = TempRepo::new();
+        repo.write("HEAD", "ref: refs/heads/feature/prompt\n");
+        fs::create_dir_all(repo.path().join("src/nested")).expect("create subdirectory");
+
+        let (work_dir, git_dir) = find_git_dir(&repo.path().join("src/nested")).expect("a repo");
+
+        assert_eq!(work_dir, repo.path());
+        assert_eq!(
+            read_head(&git_dir),
+            Some(Head::Branch("feature/prompt".into()))
+        );
+    }
+
+    #[test]
+    fn resolves_loose_and_packed_refs() {
+        let repo = TempRepo::new();
+        repo.write(
+            "refs/heads/main",
+            "1111111111111111111111111111111111111111\n",
+        );
+        repo.write(
+            "packed-refs",
+            "# pack-refs with: peeled fully-peeled sorted\n\
+             2222222222222222222222222222222222222222 refs/heads/release\n\
+             ^3333333333333333333333333333333333333333\n",
+        );
+        let git_dir = repo.path().join(".git");
+
+        assert_eq!(
+            resolve_ref(&git_dir, "refs/heads/main"),
+            Some("1111111111111111111111111111111111111111".into())
+        );
+        assert_eq!(
+            resolve_ref(&git_dir, "refs/heads/release"),
+            Some("2222222222222222222222222222222222222222".into())
+        );
+        assert_eq!(resolve_ref(&git_dir, "refs/heads/missing"), None);
+    }
+
+    #[test]
+    fn shortens_a_detached_head() {
+        let repo = TempRepo::new();
+        repo.write("HEAD", "0123456789abcdef0123456789abcdef01234567\n");
+        let mut segment = GitSegment::new();
+
+        segment.update(repo.path());
+
+        assert_eq!(segment.status().expect("a repo").branch, "0123456");
+    }
+
+    #[test]
+    fn reads_the_upstream_from_the_config() {
+        let repo = TempRepo::new();
+        repo.write(
+            "config",
+            "[core]\n\
+             \tbare = false\n\
+             [branch \"main\"]\n\
+             \tremote = origin\n\
+             \tmerge = refs/heads/trunk\n\
+             [branch \"local\"]\n\
+             \tremote = .\n\
+             \tmerge = refs/heads/main\n",
+        );
+        let git_dir = repo.path().join(".git");
+
+        assert_eq!(
+            upstream_ref(&git_dir, "main"),
+            Some("refs/remotes/origin/trunk".into())
+        );
+        assert_eq!(
+            upstream_ref(&git_dir, "local"),
+            Some("refs/heads/main".into())
+        );
+        assert_eq!(upstream_ref(&git_dir, "feature"), None);
+    }
+
+    #[test]
+    fn parses_the_porcelain_status() {
+        let clean = "# branch.oid 1111111111111111111111111111111111111111\n\
+                     # branch.head main\n";
+        let changed = "# branch.oid 1111111111111111111111111111111111111111\n\
+                       # branch.head main\n\
+                       # branch.upstream origin/main\n\
+                       # branch.ab +2 -1\n\
+                       1 .M N... 100644 100644 100644 \
+                       ce013625030ba8dba906f756967f9e9ca394464a \
+                       ce013625030ba8dba906f756967f9e9ca394464a README.md\n";
+
+        assert_eq!(parse_status(clean), StatusDetails::default());
+        assert_eq!(
+            parse_status(changed),
+            StatusDetails {
+                dirty: true,
+                ahead: 2,
+                behind: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn asks_git_for_the_status_in_the_background() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args([
+                    "-c",
+                    "user.name=A U Thor",
+                    "-c",
+                    "user.email=author@example.com",
+                ])
+                .args(args)
+                .current_dir(dir.path())
+                .output()
+                .map(|output| output.status.success())
+                .unwrap_or(false)
+        };
+        if !git(&["init", "-q"]) {
+            // No git to ask
+            return;
+        }
+        fs::write(dir.path().join("README.md"), "hello\n").expect("write file");
+        assert!(git(&["add", "README.md"]));
+        assert!(git(&["commit", "-q", "-m", "Add a readme"]));
+        fs::write(dir.path().join("README.md"), "changed\n").expect("write file");
+
+        let mut segment = GitSegment::new();
+        segment.update(dir.path());
+        let started = Instant::now();
+        while !segment.status().expect("a repo").dirty {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "git status timed out"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn caches_a_limited_number_of_repositories() {
+        let repos: Vec<_> = (0..MAX_CACHED_REPOS + 1)
+            .map(|_| {
+                let repo = TempRepo::new();
+                repo.write("HEAD", "ref: refs/heads/main\n");
+                repo
+            })
+            .collect();
+        let mut segment = GitSegment::new();
+
+        for repo in &repos {
+            segment.update(&repo.path().join(".git/refs"));
+        }
+
+        assert_eq!(segment.cache.lock().expect("cache").len(), MAX_CACHED_REPOS);
+    }
+
+    #[test]
+    fn formats_the_status() {
+        let status = GitStatus {
+            branch: "main".into(),
+            dirty: true,
+            ahead: 2,
+            behind: 1,
+        };
+
+        assert_eq!(status.to_string(), "(main* ↑2 ↓1)");
+    }
+}
@@ -0,0 +1,305 @@
+mod config;
+
+use config::{HintSource, LineEditorConfig};
+
+use crate::app::CliOptions;
+use crate::completer::NuCompleter;
+use crate::validator::NuValidator;
+
+use log::warn;
+use nu_engine::EvaluationContext;
+use nu_errors::ShellError;
+use nu_protocol::Value;
+use nu_source::Text;
+use reedline::{
+    default_emacs_keybindings, default_vi_insert_keybindings, default_vi_normal_keybindings,
+    BackgroundCompleter, DefaultCompletionActionHandler, DefaultHighlighter, DefaultHinter,
+    EditMode, FileBackedHistory, Prompt, Reedline, Signal, HISTORY_SIZE,
+};
+
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Name of the history file next to `config.toml` in the nushell config directory
+const HISTORY_FILE_NAME: &str = "history.txt";
+
+/// How long Tab waits for completions, slower ones are offered when Tab is pressed again
+const TAB_COMPLETION_BUDGET: Duration = Duration::from_secs(1);
+
+/// The line editor of an interactive session.
+///
+/// It is built once and reused for every prompt, so the history, undo state, edit mode and
+/// cut buffer carry over from one command to the next. The configurable parts are applied
+/// again whenever the `line_editor` section of the nushell config changes, the history
+/// whenever its settings change and the highlighter whenever the commands in scope change.
+pub struct EditorSession {
+    line_editor: Reedline,
+    // The `line_editor` config section the editor was last configured with
+    config: Option<Value>,
+    settings: LineEditorConfig,
+    // The commands the highlighter knows, `None` until it is built for the current settings
+    commands: Option<Vec<String>>,
+    save_history: bool,
+    history: HistorySettings,
+    // The last problem with the history settings, so it is only reported once
+    history_error: Option<ShellError>,
+}
+
+/// Where the history is kept and how many entries it holds
+#[derive(Debug, Clone, PartialEq)]
+struct HistorySettings {
+    size: usize,
+    path: Option<PathBuf>,
+}
+
+impl EditorSession {
+    pub fn new(context: &EvaluationContext, options: &CliOptions) -> Result<Self, Box<dyn Error>> {
+        let config = line_editor_config(context);
+        let settings = parse_config(context, config.as_ref());
+        let history = history_settings(context, &settings, options.save_history)?;
+        let line_editor = Reedline::new().with_history(Box::new(open_history(&history)?))?;
+
+        let mut session = Self {
+            line_editor: configure(line_editor, &settings, context),
+            config,
+            settings,
+            commands: None,
+            save_history: options.save_history,
+            history,
+            history_error: None,
+        };
+        session.update_highlighter(context);
+
+        Ok(session)
+    }
+
+    /// Reconfigure the line editor if the configuration changed since it was last applied,
+    /// and highlight the commands defined by the previous command
+    pub fn reload_config(&mut self, context: &EvaluationContext) {
+        let config = line_editor_config(context);
+        if config != self.config {
+            let settings = parse_config(context, config.as_ref());
+            self.line_editor = configure(std::mem::take(&mut self.line_editor), &settings, context);
+            self.config = config;
+            self.settings = settings;
+            self.commands = None;
+        }
+        self.reload_history(context);
+        self.update_highlighter(context);
+    }
+
+    /// Reopen the history if its settings changed, which also depend on the top-level
+    /// `history_size` and `history_path` keys outside of the `line_editor` section
+    fn reload_history(&mut self, context: &EvaluationContext) {
+        let reopened =
+            history_settings(context, &self.settings, self.save_history).and_then(|history| {
+                if history == self.history {
+                    return Ok(None);
+                }
+                open_history(&history)
+                    .map(|reopened| Some((history, reopened)))
+                    .map_err(|err| ShellError::untagged_runtime_error(err.to_string()))
+            });
+
+        match reopened {
+            Ok(Some((history, reopened))) => {
+                self.line_editor = std::mem::take(&mut self.line_editor)
+                    .with_history(Box::new(reopened))
+                    .expect("Error configuring reedline with history");
+                self.history = history;
+                self.history_error = None;
+            }
+            Ok(None) => self.history_error = None,
+            Err(err) => {
+                if self.history_error.as_ref() != Some(&err) {
+                    report_error(context, err.clone());
+                    self.history_error = Some(err);
+                }
+            }
+        }
+    }
+
+    /// Build the highlighter again if commands were added to or removed from the scope, like
+    /// ones defined with `def` or `alias`
+    fn update_highlighter(&mut self, context: &EvaluationContext) {
+        let commands = context.scope.get_command_names();
+        if self.commands.as_ref() == Some(&commands) {
+            return;
+        }
+
+        let mut highlighter = DefaultHighlighter::new(commands.clone());
+        highlighter.change_colors(
+            self.settings.match_color,
+            self.settings.notmatch_color,
+            self.settings.neutral_color,
+        );
+        self.line_editor =
+            std::mem::take(&mut self.line_editor).with_highlighter(Box::new(highlighter));
+        self.commands = Some(commands);
+    }
+
+    pub fn read_line(&mut self, prompt: &dyn Prompt) -> crossterm::Result<Signal> {
+        let signal = self.line_editor.read_line(prompt);
+        for err in self.line_editor.take_history_errors() {
+            warn!("Failed to write the history file: {}", err);
+        }
+        signal
+    }
+
+    pub fn print_history(&mut self) -> crossterm::Result<()> {
+        self.line_editor.print_history()
+    }
+
+    pub fn print_crlf(&mut self) -> crossterm::Result<()> {
+        self.line_editor.print_crlf()
+    }
+
+    pub fn clear_screen(&mut self) -> crossterm::Result<()> {
+        self.line_editor.clear_screen()
+    }
+}
+
+fn line_editor_config(context: &EvaluationContext) -> Option<Value> {
+    context
+        .configs()
+        .lock()
+        .global_config
+        .as_ref()
+        .and_then(|cfg| cfg.var("line_editor"))
+}
+
+/// Parse the `line_editor` section, an invalid section is reported and the defaults are used
+fn parse_config(context: &EvaluationContext, config: Option<&Value>) -> LineEditorConfig {
+    match config.map(LineEditorConfig::from_value) {
+        Some(Ok(settings)) => settings,
+        Some(Err(err)) => {
+            report_error(context, err);
+            LineEditorConfig::default()
+        }
+        None => LineEditorConfig::default(),
+    }
+}
+
+fn report_error(context: &EvaluationContext, err: ShellError) {
+    context.host().lock().print_err(err, &Text::from(""));
+}
+
+/// Resolve the history settings, persisted next to the nushell config unless `--no-history`
+/// was passed.
+///
+/// The `history_size` and `history_path` keys of the `line_editor` section take precedence
+/// over the top-level keys of the same name.
+fn history_settings(
+    context: &EvaluationContext,
+    settings: &LineEditorConfig,
+    save_history: bool,
+) -> Result<HistorySettings, ShellError> {
+    let (size, path) = if let Some(cfg) = &context.configs().lock().global_config {
+        (cfg.var("history_size"), cfg.var("history_path"))
+    } else {
+        (None, None)
+    };
+
+    let size = match (settings.history_size, size) {
+        (Some(size), _) => size,
+        (None, Some(size)) => size.as_u64()? as usize,
+        (None, None) => HISTORY_SIZE,
+    };
+
+    if !save_history {
+        return Ok(HistorySettings { size, path: None });
+    }
+
+    let path = match (&settings.history_path, path) {
+        (Some(path), _) => path.clone(),
+        (None, Some(path)) => PathBuf::from(path.as_string()?),
+        (None, None) => default_history_path()?,
+    };
+
+    Ok(HistorySettings {
+        size,
+        path: Some(path),
+    })
+}
+
+/// Open the history, problems the history file could be recovered from are logged
+fn open_history(history: &HistorySettings) -> std::io::Result<FileBackedHistory> {
+    match &history.path {
+        Some(path) => {
+            let history = FileBackedHistory::with_file(history.size, path.clone())?;
+            for warning in history.warnings() {
+                warn!("{}", warning);
+            }
+            Ok(history)
+        }
+        None => Ok(FileBackedHistory::new(history.size)),
+    }
+}
+
+fn default_history_path() -> Result<PathBuf, ShellError> {
+    let mut path = nu_data::config::default_path()?;
+    path.set_file_name(HISTORY_FILE_NAME);
+
+    Ok(path)
+}
+
+/// Apply the configurable parts of the line editor except the highlighter, keeps history and
+/// editing state
+fn configure(
+    mut line_editor: Reedline,
+    settings: &LineEditorConfig,
+    context: &EvaluationContext,
+) -> Reedline {
+    // Every mode starts from its defaults, so overrides of a previously configured mode are
+    // dropped when the edit mode changes
+    let modes = vec![
+        (EditMode::Emacs, default_emacs_keybindings()),
+        (EditMode::ViInsert, default_vi_insert_keybindings()),
+        (EditMode::ViNormal, default_vi_normal_keybindings()),
+    ];
+    for (mode, mut keybindings) in modes {
+        if settings.edit_mode.includes(mode) {
+            for binding in &settings.keybindings {
+                keybindings.add_binding(
+                    binding.modifier,
+                    binding.key_code,
+                    binding.commands.clone(),
+                );
+            }
+        }
+        line_editor = line_editor.with_edit_mode_keybindings(mode, keybindings);
+    }
+
+    let completer = NuCompleter::new(context.clone());
+
+    let mut hinter = DefaultHinter::default().with_style(settings.hint_style);
+    match settings.hints {
+        HintSource::Completions => {
+            // Hints are computed on every keystroke, so looking up commands on `PATH` must
+            // not hold up typing
+            hinter = hinter.with_suggestion_completer(Box::new(BackgroundCompleter::new(Box::new(
+                completer
+                    .clone()
+                    .with_min_word_len(settings.completion_min_word_len),
+            ))))
+        }
+        HintSource::History => hinter = hinter.with_history(),
+        HintSource::Disabled => {}
+    }
+    if settings.hint_inside_line {
+        hinter = hinter.with_inside_line();
+    }
+
+    line_editor
+        .with_edit_mode(settings.edit_mode.into())
+        .with_validator(Box::new(NuValidator {}))
+        .with_completion_action_handler(Box::new(
+            DefaultCompletionActionHandler::default().with_suggestion_completer(Box::new(
+                // Tab may wait longer than a keystroke, but a hanging `PATH` lookup must not
+                // freeze the prompt either
+                BackgroundCompleter::new(Box::new(completer)).with_budget(TAB_COMPLETION_BUDGET),
+            )),
+        ))
+        .with_hinter(Box::new(hinter))
+}
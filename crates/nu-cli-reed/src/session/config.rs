@@ -0,0 +1,488 @@
+use crossterm::event::{KeyCode, KeyModifiers};
+use nu_ansi_term::{Color, Style};
+use nu_errors::ShellError;
+use nu_protocol::{UntaggedValue, Value};
+use reedline::{EditCommand, EditMode};
+
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// Where the hints shown after the cursor come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HintSource {
+    Completions,
+    History,
+    Disabled,
+}
+
+/// The settings of the `line_editor` section of the nushell config.
+///
+/// ```toml
+/// [line_editor]
+/// edit_mode = "vi"
+/// history_size = 10000
+/// hints = "history"
+/// hint_style = { fg = "dark_gray", attr = "i" }
+/// highlight = { match = "green", notmatch = "red", neutral = "white" }
+/// completion_min_word_len = 2
+///
+/// [[line_editor.keybindings]]
+/// modifier = "alt"
+/// key = "m"
+/// commands = ["BackspaceWord"]
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineEditorConfig {
+    pub edit_mode: LineEditMode,
+    pub keybindings: Vec<KeybindingConfig>,
+    pub history_size: Option<usize>,
+    pub history_path: Option<PathBuf>,
+    pub hints: HintSource,
+    pub hint_inside_line: bool,
+    pub hint_style: Style,
+    pub match_color: Color,
+    pub notmatch_color: Color,
+    pub neutral_color: Color,
+    pub completion_min_word_len: usize,
+}
+
+/// The edit modes that can be picked in the config, vi starts in insert mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEditMode {
+    Emacs,
+    Vi,
+}
+
+impl LineEditMode {
+    /// Whether the reedline mode is part of this edit mode, vi has an insert and a normal mode
+    pub fn includes(self, mode: EditMode) -> bool {
+        match self {
+            LineEditMode::Emacs => mode == EditMode::Emacs,
+            LineEditMode::Vi => mode != EditMode::Emacs,
+        }
+    }
+}
+
+impl From<LineEditMode> for EditMode {
+    fn from(mode: LineEditMode) -> Self {
+        match mode {
+            LineEditMode::Emacs => EditMode::Emacs,
+            LineEditMode::Vi => EditMode::ViInsert,
+        }
+    }
+}
+
+/// A key combination bound to a list of edit commands, replacing the default binding of the
+/// configured edit mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeybindingConfig {
+    pub modifier: KeyModifiers,
+    pub key_code: KeyCode,
+    pub commands: Vec<EditCommand>,
+}
+
+impl Default for LineEditorConfig {
+    fn default() -> Self {
+        Self {
+            edit_mode: LineEditMode::Emacs,
+            keybindings: vec![KeybindingConfig {
+                modifier: KeyModifiers::ALT,
+                key_code: KeyCode::Char('m'),
+                commands: vec![EditCommand::BackspaceWord],
+            }],
+            history_size: None,
+            history_path: None,
+            hints: HintSource::Completions,
+            hint_inside_line: false,
+            hint_style: Style::new().italic().fg(Color::LightGray),
+            match_color: Color::Green,
+            notmatch_color: Color::Red,
+            neutral_color: Color::White,
+            completion_min_word_len: 2,
+        }
+    }
+}
+
+impl LineEditorConfig {
+    /// Read the settings from the `line_editor` config section, keys left out keep their default
+    pub fn from_value(value: &Value) -> Result<Self, ShellError> {
+        let mut config = Self::default();
+
+        for (key, value) in row_entries(value)? {
+            match key.as_str() {
+                "edit_mode" => config.edit_mode = edit_mode(value)?,
+                "keybindings" => config.keybindings = keybindings(value)?,
+                "history_size" => config.history_size = Some(value.as_u64()? as usize),
+                "history_path" => config.history_path = Some(PathBuf::from(value.as_string()?)),
+                "hints" => config.hints = hint_source(value)?,
+                "hint_inside_line" => config.hint_inside_line = value.as_bool()?,
+                "hint_style" => config.hint_style = style(value)?,
+                "highlight" => {
+                    for (key, value) in row_entries(value)? {
+                        match key.as_str() {
+                            "match" => config.match_color = color(value)?,
+                            "notmatch" => config.notmatch_color = color(value)?,
+                            "neutral" => config.neutral_color = color(value)?,
+                            _ => return Err(unknown_key("line_editor.highlight", key, value)),
+                        }
+                    }
+                }
+                "completion_min_word_len" => {
+                    config.completion_min_word_len = value.as_u64()? as usize
+                }
+                _ => return Err(unknown_key("line_editor", key, value)),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn row_entries(value: &Value) -> Result<impl Iterator<Item = (&String, &Value)>, ShellError> {
+    match &value.value {
+        UntaggedValue::Row(dict) => Ok(dict.entries.iter()),
+        _ => Err(ShellError::labeled_error(
+            "Expected a table of settings",
+            "expected a table",
+            value.tag.span,
+        )),
+    }
+}
+
+fn unknown_key(section: &str, key: &str, value: &Value) -> ShellError {
+    ShellError::labeled_error(
+        format!("Unknown setting `{}` in `{}`", key, section),
+        "unknown setting",
+        value.tag.span,
+    )
+}
+
+fn edit_mode(value: &Value) -> Result<LineEditMode, ShellError> {
+    match value.as_string()?.as_str() {
+        "emacs" => Ok(LineEditMode::Emacs),
+        "vi" => Ok(LineEditMode::Vi),
+        _ => Err(ShellError::labeled_error(
+            "Unknown edit mode",
+            "expected `emacs` or `vi`",
+            value.tag.span,
+        )),
+    }
+}
+
+fn hint_source(value: &Value) -> Result<HintSource, ShellError> {
+    match value.as_string()?.as_str() {
+        "completions" => Ok(HintSource::Completions),
+        "history" => Ok(HintSource::History),
+        "none" => Ok(HintSource::Disabled),
+        _ => Err(ShellError::labeled_error(
+            "Unknown hint source",
+            "expected `completions`, `history` or `none`",
+            value.tag.span,
+        )),
+    }
+}
+
+fn keybindings(value: &Value) -> Result<Vec<KeybindingConfig>, ShellError> {
+    let bindings = match &value.value {
+        UntaggedValue::Table(bindings) => bindings,
+        _ => {
+            return Err(ShellError::labeled_error(
+                "Expected a list of keybindings",
+                "expected a list",
+                value.tag.span,
+            ))
+        }
+    };
+
+    bindings.iter().map(keybinding).collect()
+}
+
+fn keybinding(value: &Value) -> Result<KeybindingConfig, ShellError> {
+    let mut modifier = KeyModifiers::NONE;
+    let mut key_code = None;
+    let mut commands = None;
+
+    for (key, value) in row_entries(value)? {
+        match key.as_str() {
+            "modifier" => modifier = key_modifiers(value)?,
+            "key" => key_code = Some(key_code_from(value)?),
+            "commands" => commands = Some(edit_commands(value)?),
+            _ => return Err(unknown_key("line_editor.keybindings", key, value)),
+        }
+    }
+
+    match (key_code, commands) {
+        (Some(key_code), Some(commands)) => Ok(KeybindingConfig {
+            modifier,
+            key_code,
+            commands,
+        }),
+        _ => Err(ShellError::labeled_error(
+            "Incomplete keybinding",
+            "a keybinding needs a `key` and its `commands`",
+            value.tag.span,
+        )),
+    }
+}
+
+/// Modifiers are joined with `+`, like `control+alt`
+fn key_modifiers(value: &Value) -> Result<KeyModifiers, ShellError> {
+    let mut modifiers = KeyModifiers::NONE;
+    for name in value.as_string()?.to_lowercase().split('+') {
+        modifiers |= match name.trim() {
+            "control" | "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            "none" => KeyModifiers::NONE,
+            _ => {
+                return Err(ShellError::labeled_error(
+                    format!("Unknown key modifier `{}`", name),
+                    "expected `control`, `alt`, `shift` or `none`",
+                    value.tag.span,
+                ))
+            }
+        };
+    }
+
+    Ok(modifiers)
+}
+
+/// A single character is that character key, anything else is a serialized `KeyCode`
+/// like `Enter`, `Left` or `F: 1`
+fn key_code_from(value: &Value) -> Result<KeyCode, ShellError> {
+    let key = value.as_string()?;
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(KeyCode::Char(c));
+    }
+
+    serde_yaml::from_str(&key).map_err(|e| {
+        ShellError::labeled_error(
+            format!("Unknown key `{}`: {}", key, e),
+            "unknown key",
+            value.tag.span,
+        )
+    })
+}
+
+/// Each command is a serialized `EditCommand`, like `BackspaceWord` or `InsertChar: x`
+fn edit_commands(value: &Value) -> Result<Vec<EditCommand>, ShellError> {
+    let commands = match &value.value {
+        UntaggedValue::Table(commands) => commands.iter().collect(),
+        _ => vec![value],
+    };
+
+    commands
+        .into_iter()
+        .map(|command| {
+            let text = command.as_string()?;
+            serde_yaml::from_str(&text).map_err(|e| {
+                ShellError::labeled_error(
+                    format!("Unknown edit command `{}`: {}", text, e),
+                    "unknown edit command",
+                    command.tag.span,
+                )
+            })
+        })
+        .collect()
+}
+
+/// A style is either a color name or a table with `fg`, `bg` and `attr` like in `color_config`
+fn style(value: &Value) -> Result<Style, ShellError> {
+    if let UntaggedValue::Primitive(_) = &value.value {
+        return Ok(Style::new().fg(color(value)?));
+    }
+
+    let mut style = Style::new();
+    for (key, value) in row_entries(value)? {
+        match key.as_str() {
+            "fg" => style.foreground = Some(color(value)?),
+            "bg" => style.background = Some(color(value)?),
+            "attr" => {
+                for attr in value.as_string()?.to_lowercase().chars() {
+                    match attr {
+                        'b' => style.is_bold = true,
+                        'd' => style.is_dimmed = true,
+                        'i' => style.is_italic = true,
+                        'u' => style.is_underline = true,
+                        'l' => style.is_blink = true,
+                        'r' => style.is_reverse = true,
+                        'h' => style.is_hidden = true,
+                        's' => style.is_strikethrough = true,
+                        _ => {}
+                    }
+                }
+            }
+            _ => return Err(unknown_key("line_editor.hint_style", key, value)),
+        }
+    }
+
+    Ok(style)
+}
+
+/// A color name like `light_gray` or a hex color like `#ffcfff`
+fn color(value: &Value) -> Result<Color, ShellError> {
+    let name = value.as_string()?.to_lowercase();
+    let color = match name.as_str() {
+        "black" => Color::Black,
+        "dark_gray" => Color::DarkGray,
+        "red" => Color::Red,
+        "light_red" => Color::LightRed,
+        "green" => Color::Green,
+        "light_green" => Color::LightGreen,
+        "yellow" => Color::Yellow,
+        "light_yellow" => Color::LightYellow,
+        "blue" => Color::Blue,
+        "light_blue" => Color::LightBlue,
+        "purple" => Color::Purple,
+        "light_purple" => Color::LightPurple,
+        "magenta" => Color::Magenta,
+        "light_magenta" => Color::LightMagenta,
+        "cyan" => Color::Cyan,
+        "light_cyan" => Color::LightCyan,
+        "white" => Color::White,
+        "light_gray" => Color::LightGray,
+        hex if hex.starts_with('#') && hex.len() == 7 => {
+            let component = |range: Range<usize>| u8::from_str_radix(&hex[range], 16);
+            match (component(1..3), component(3..5), component(5..7)) {
+                (Ok(r), Ok(g), Ok(b)) => Color::Rgb(r, g, b),
+                _ => return Err(unknown_color(value)),
+            }
+        }
+        _ => return Err(unknown_color(value)),
+    };
+
+    Ok(color)
+}
+
+fn unknown_color(value: &Value) -> ShellError {
+    ShellError::labeled_error(
+        "Unknown color",
+        "expected a color name or a `#rrggbb` hex color",
+        value.tag.span,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HintSource, KeybindingConfig, LineEditMode, LineEditorConfig};
+    use crossterm::event::{KeyCode, KeyModifiers};
+    use nu_ansi_term::{Color, Style};
+    use nu_protocol::{TaggedDictBuilder, UntaggedValue, Value};
+    use nu_source::Tag;
+    use reedline::{EditCommand, EditMode};
+
+    fn string(s: &str) -> Value {
+        UntaggedValue::string(s).into_untagged_value()
+    }
+
+    fn row(entries: Vec<(&str, Value)>) -> Value {
+        let mut dict = TaggedDictBuilder::new(Tag::unknown());
+        for (key, value) in entries {
+            dict.insert_value(key, value);
+        }
+        dict.into_value()
+    }
+
+    fn table(rows: Vec<Value>) -> Value {
+        UntaggedValue::Table(rows).into_untagged_value()
+    }
+
+    #[test]
+    fn empty_section_keeps_the_defaults() {
+        let config = LineEditorConfig::from_value(&row(vec![])).expect("valid config");
+
+        assert_eq!(config, LineEditorConfig::default());
+    }
+
+    #[test]
+    fn reads_the_settings() {
+        let section = row(vec![
+            ("edit_mode", string("vi")),
+            ("history_size", UntaggedValue::int(10).into_untagged_value()),
+            ("hints", string("history")),
+            (
+                "hint_style",
+                row(vec![("fg", string("#ffcfff")), ("attr", string("iu"))]),
+            ),
+            ("highlight", row(vec![("match", string("light_blue"))])),
+        ]);
+
+        let config = LineEditorConfig::from_value(&section).expect("valid config");
+
+        assert_eq!(config.edit_mode, LineEditMode::Vi);
+        assert_eq!(config.history_size, Some(10));
+        assert_eq!(config.hints, HintSource::History);
+        assert_eq!(
+            config.hint_style,
+            Style::new()
+                .italic()
+                .underline()
+                .fg(Color::Rgb(255, 207, 255))
+        );
+        assert_eq!(config.match_color, Color::LightBlue);
+        assert_eq!(config.notmatch_color, Color::Red);
+    }
+
+    #[test]
+    fn reads_serialized_keybindings() {
+        let section = row(vec![(
+            "keybindings",
+            table(vec![
+                row(vec![
+                    ("modifier", string("control+alt")),
+                    ("key", string("x")),
+                    (
+                        "commands",
+                        table(vec![string("CutToEnd"), string("InsertChar: y")]),
+                    ),
+                ]),
+                row(vec![
+                    ("key", string("Home")),
+                    ("commands", string("MoveToStart")),
+                ]),
+            ]),
+        )]);
+
+        let config = LineEditorConfig::from_value(&section).expect("valid config");
+
+        assert_eq!(
+            config.keybindings,
+            vec![
+                KeybindingConfig {
+                    modifier: KeyModifiers::CONTROL | KeyModifiers::ALT,
+                    key_code: KeyCode::Char('x'),
+                    commands: vec![EditCommand::CutToEnd, EditCommand::InsertChar('y')],
+                },
+                KeybindingConfig {
+                    modifier: KeyModifiers::NONE,
+                    key_code: KeyCode::Home,
+                    commands: vec![EditCommand::MoveToStart],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        assert!(LineEditorConfig::from_value(&row(vec![("edit_mode", string("ed"))])).is_err());
+        assert!(LineEditorConfig::from_value(&row(vec![("colour", string("red"))])).is_err());
+        assert!(LineEditorConfig::from_value(&row(vec![(
+            "keybindings",
+            table(vec![row(vec![
+                ("key", string("x")),
+                ("commands", string("Fly"))
+            ])]),
+        )]))
+        .is_err());
+    }
+
+    #[test]
+    fn vi_keybindings_apply_to_insert_and_normal_mode() {
+        assert!(LineEditMode::Vi.includes(EditMode::ViInsert));
+        assert!(LineEditMode::Vi.includes(EditMode::ViNormal));
+        assert!(!LineEditMode::Vi.includes(EditMode::Emacs));
+        assert!(LineEditMode::Emacs.includes(EditMode::Emacs));
+        assert!(!LineEditMode::Emacs.includes(EditMode::ViInsert));
+    }
+}
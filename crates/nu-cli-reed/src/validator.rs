@@ -0,0 +1,87 @@
+use nu_errors::ParseErrorReason;
+use nu_parser::NewlineMode;
+use reedline::{ValidationResult, Validator};
+
+/// Keeps the line editor collecting lines while the input is not a complete nushell block.
+///
+/// Input is incomplete when the lexer or the block parser run into the end of the input while
+/// expecting more (an unclosed `{`, `[`, `(` or quote) or when the last pipeline ends in a `|`.
+pub struct NuValidator {}
+
+impl Validator for NuValidator {
+    fn validate(&self, line: &str) -> ValidationResult {
+        let (tokens, err) = nu_parser::lex(line, 0, NewlineMode::Normal);
+        if let Some(err) = err {
+            if let ParseErrorReason::Eof { .. } = err.reason() {
+                return ValidationResult::Incomplete;
+            }
+        }
+
+        let (_, err) = nu_parser::parse_block(tokens);
+        if let Some(err) = err {
+            if let ParseErrorReason::Eof { .. } = err.reason() {
+                return ValidationResult::Incomplete;
+            }
+        }
+
+        if line.trim_end().ends_with('|') {
+            return ValidationResult::Incomplete;
+        }
+
+        ValidationResult::Complete
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NuValidator;
+    use reedline::{ValidationResult, Validator};
+
+    fn validate(line: &str) -> ValidationResult {
+        NuValidator {}.validate(line)
+    }
+
+    #[test]
+    fn complete_input_is_submitted() {
+        assert_eq!(
+            validate("ls | where size > 10kb"),
+            ValidationResult::Complete
+        );
+        assert_eq!(
+            validate("ls | each { echo $it.name }"),
+            ValidationResult::Complete
+        );
+        assert_eq!(validate("echo \"a | b\""), ValidationResult::Complete);
+        assert_eq!(validate(""), ValidationResult::Complete);
+    }
+
+    #[test]
+    fn unclosed_delimiters_are_incomplete() {
+        assert_eq!(validate("ls | each {"), ValidationResult::Incomplete);
+        assert_eq!(validate("echo [1 2"), ValidationResult::Incomplete);
+        assert_eq!(validate("echo (1 +"), ValidationResult::Incomplete);
+        assert_eq!(
+            validate("echo \"unterminated"),
+            ValidationResult::Incomplete
+        );
+        assert_eq!(validate("echo 'unterminated"), ValidationResult::Incomplete);
+    }
+
+    #[test]
+    fn trailing_pipe_is_incomplete() {
+        assert_eq!(validate("ls |"), ValidationResult::Incomplete);
+        assert_eq!(validate("ls | \n"), ValidationResult::Incomplete);
+    }
+
+    #[test]
+    fn multiline_block_is_complete_once_balanced() {
+        assert_eq!(
+            validate("ls | each {\n  echo $it.name\n"),
+            ValidationResult::Incomplete
+        );
+        assert_eq!(
+            validate("ls | each {\n  echo $it.name\n}"),
+            ValidationResult::Complete
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use {
+    crate::{Suggestion, SuggestionCompleter},
+    std::{
+        cell::RefCell,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+            Arc,
+        },
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+/// How long a [`BackgroundCompleter`] waits for suggestions before letting the editor continue
+pub static DEFAULT_COMPLETION_BUDGET: Duration = Duration::from_millis(50);
+
+/// Runs a slow [`SuggestionCompleter`] on a background thread, so it can't freeze typing.
+///
+/// A new line or cursor position cancels the request for the previous one: requests that
+/// became outdated are skipped by the worker and their results are dropped. Suggestions that
+/// take longer than the time budget are returned by a later call for the same line and
+/// position, the editor repaints the hint as soon as they arrive. Behind a completion action
+/// handler the budget bounds how long Tab blocks, late suggestions are used by the next Tab.
+///
+/// # Example
+/// ```rust,no_run
+/// use reedline::{BackgroundCompleter, DefaultHinter, FilenameCompleter, Reedline};
+/// use std::time::Duration;
+///
+/// let completer = BackgroundCompleter::new(Box::new(FilenameCompleter::new()))
+///     .with_budget(Duration::from_millis(20));
+///
+/// let mut line_editor = Reedline::new().with_hinter(Box::new(
+///     DefaultHinter::default().with_suggestion_completer(Box::new(completer)),
+/// ));
+/// ```
+pub struct BackgroundCompleter {
+    requests: Sender<Request>,
+    responses: Receiver<Response>,
+    // The latest request, the worker drops the ones that are behind it
+    generation: Arc<AtomicUsize>,
+    budget: Duration,
+    state: RefCell<State>,
+}
+
+struct Request {
+    generation: usize,
+    line: String,
+    pos: usize,
+}
+
+struct Response {
+    generation: usize,
+    suggestions: Vec<Suggestion>,
+}
+
+#[derive(Default)]
+struct State {
+    // The line and position of the latest request
+    requested: Option<(String, usize)>,
+    // Its suggestions, once they arrived
+    suggestions: Option<Vec<Suggestion>>,
+}
+
+impl BackgroundCompleter {
+    /// Move the completer to a worker thread, which stops when the `BackgroundCompleter` is
+    /// dropped
+    pub fn new(completer: Box<dyn SuggestionCompleter + Send>) -> BackgroundCompleter {
+        let (requests, worker_requests) = channel::<Request>();
+        let (worker_responses, responses) = channel();
+        let generation = Arc::new(AtomicUsize::new(0));
+
+        let latest = generation.clone();
+        thread::spawn(move || {
+            while let Ok(mut request) = worker_requests.recv() {
+                // Only the most recent request is still of interest
+                while let Ok(newer) = worker_requests.try_recv() {
+                    request = newer;
+                }
+                if request.generation != latest.load(Ordering::SeqCst) {
+                    continue;
+                }
+
+                let suggestions = completer.suggest(&request.line, request.pos);
+                if request.generation != latest.load(Ordering::SeqCst) {
+                    continue;
+                }
+
+                let response = Response {
+                    generation: request.generation,
+                    suggestions,
+                };
+                if worker_responses.send(response).is_err() {
+                    break;
+                }
+            }
+        });
+
+        BackgroundCompleter {
+            requests,
+            responses,
+            generation,
+            budget: DEFAULT_COMPLETION_BUDGET,
+            state: RefCell::new(State::default()),
+        }
+    }
+
+    /// A builder that sets how long to wait for the suggestions of a new line before returning
+    /// without them
+    pub fn with_budget(mut self, budget: Duration) -> BackgroundCompleter {
+        self.budget = budget;
+        self
+    }
+
+    // Take the response to the latest request if it arrives before the deadline, responses to
+    // earlier requests are dropped
+    fn receive(&self, state: &mut State, deadline: Option<Instant>) {
+        let generation = self.generation.load(Ordering::SeqCst);
+
+        loop {
+            let response = match deadline {
+                Some(deadline) => {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    match self.responses.recv_timeout(timeout) {
+                        Ok(response) => response,
+                        Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => {
+                            return
+                        }
+                    }
+                }
+                None => match self.responses.try_recv() {
+                    Ok(response) => response,
+                    Err(_) => return,
+                },
+            };
+
+            if response.generation == generation {
+                state.suggestions = Some(response.suggestions);
+                return;
+            }
+        }
+    }
+}
+
+impl SuggestionCompleter for BackgroundCompleter {
+    fn suggest(&self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let mut state = self.state.borrow_mut();
+        let request = (line.to_string(), pos);
+
+        if state.requested.as_ref() != Some(&request) {
+            let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+            state.requested = Some(request);
+            state.suggestions = None;
+
+            let sent = self.requests.send(Request {
+                generation,
+                line: line.to_string(),
+                pos,
+            });
+            if sent.is_ok() {
+                self.receive(&mut state, Some(Instant::now() + self.budget));
+            }
+        } else if state.suggestions.is_none() {
+            self.receive(&mut state, None);
+        }
+
+        state.suggestions.clone().unwrap_or_default()
+    }
+
+    fn is_pending(&self) -> bool {
+        let state = self.state.borrow();
+        state.requested.is_some() && state.suggestions.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Span;
+    use pretty_assertions::assert_eq;
+
+    // Suggests the line itself after a delay
+    struct SlowCompleter(Duration);
+
+    impl SuggestionCompleter for SlowCompleter {
+        fn suggest(&self, line: &str, pos: usize) -> Vec<Suggestion> {
+            thread::sleep(self.0);
+            vec![Suggestion::new(format!("{}!", line), Span::new(0, pos))]
+        }
+    }
+
+    // Ask again until the suggestions arrived, panics if they take longer than a few seconds
+    fn wait_for_suggestions(
+        completer: &BackgroundCompleter,
+        line: &str,
+        pos: usize,
+    ) -> Vec<Suggestion> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let suggestions = completer.suggest(line, pos);
+            if !completer.is_pending() {
+                return suggestions;
+            }
+            assert!(Instant::now() < deadline, "suggestions never arrived");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn values(suggestions: Vec<Suggestion>) -> Vec<String> {
+        suggestions
+            .into_iter()
+            .map(|suggestion| suggestion.value)
+            .collect()
+    }
+
+    #[test]
+    fn suggestions_within_the_budget_are_returned_right_away() {
+        let completer = BackgroundCompleter::new(Box::new(SlowCompleter(Duration::from_millis(0))))
+            .with_budget(Duration::from_secs(5));
+
+        assert_eq!(values(completer.suggest("ls", 2)), vec!["ls!"]);
+        assert!(!completer.is_pending());
+    }
+
+    #[test]
+    fn slow_suggestions_arrive_on_a_later_call() {
+        let completer =
+            BackgroundCompleter::new(Box::new(SlowCompleter(Duration::from_millis(100))))
+                .with_budget(Duration::from_millis(1));
+
+        assert!(completer.suggest("ls", 2).is_empty());
+        assert!(completer.is_pending());
+
+        assert_eq!(
+            values(wait_for_suggestions(&completer, "ls", 2)),
+            vec!["ls!"]
+        );
+    }
+
+    #[test]
+    fn a_changed_line_cancels_the_previous_request() {
+        let completer =
+            BackgroundCompleter::new(Box::new(SlowCompleter(Duration::from_millis(50))))
+                .with_budget(Duration::from_millis(1));
+
+        assert!(completer.suggest("l", 1).is_empty());
+        assert!(completer.suggest("ls", 2).is_empty());
+
+        assert_eq!(
+            values(wait_for_suggestions(&completer, "ls", 2)),
+            vec!["ls!"]
+        );
+    }
+}
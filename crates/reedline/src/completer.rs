@@ -1,9 +1,10 @@
 use {
-    crate::line_buffer::LineBuffer,
+    crate::{fuzzy::fuzzy_score, line_buffer::LineBuffer, CompletionMenu},
+    crossterm::event::{KeyCode, KeyModifiers},
     std::{
         collections::{BTreeMap, BTreeSet},
-        rc::Rc,
         str::Chars,
+        sync::Arc,
     },
 };
 
@@ -41,11 +42,27 @@ pub trait ComplationActionHandler {
 
     /// Reset the index, often as a result of the user moving away from the completion
     fn reset_index(&mut self);
+
+    /// Handle a key press while a completion is in progress and return whether it was used.
+    /// Keys that are not used are handled by the editor as usual
+    fn handle_key(
+        &mut self,
+        _line: &mut LineBuffer,
+        _modifiers: KeyModifiers,
+        _code: KeyCode,
+    ) -> bool {
+        false
+    }
+
+    /// The completion menu to paint below the buffer, for handlers that show one
+    fn menu(&mut self) -> Option<&mut CompletionMenu> {
+        None
+    }
 }
 
 /// A simple handler that will do a cycle-based rotation through the options given by the Completer
 pub struct DefaultCompletionActionHandler {
-    completer: Box<dyn Completer>,
+    completer: Box<dyn SuggestionCompleter>,
     initial_line: LineBuffer,
     index: usize,
 }
@@ -72,6 +89,15 @@ impl DefaultCompletionActionHandler {
     pub fn with_completer(
         mut self,
         completer: Box<dyn Completer>,
+    ) -> DefaultCompletionActionHandler {
+        self.completer = Box::new(CompleterAdapter::new(completer));
+        self
+    }
+
+    /// A builder that configures a completer returning [`Suggestion`]s
+    pub fn with_suggestion_completer(
+        mut self,
+        completer: Box<dyn SuggestionCompleter>,
     ) -> DefaultCompletionActionHandler {
         self.completer = completer;
         self
@@ -80,7 +106,7 @@ impl DefaultCompletionActionHandler {
 impl Default for DefaultCompletionActionHandler {
     fn default() -> Self {
         DefaultCompletionActionHandler {
-            completer: Box::new(DefaultCompleter::default()),
+            completer: Box::new(CompleterAdapter::new(Box::new(DefaultCompleter::default()))),
             initial_line: LineBuffer::new(),
             index: 0,
         }
@@ -94,29 +120,20 @@ impl ComplationActionHandler for DefaultCompletionActionHandler {
     // If internal index is 0 it means that is the first tab event pressed.
     // If internal index is greater than completions vector, we bring it back to 0.
     fn handle(&mut self, line: &mut LineBuffer) {
+        // The buffer is copied as a whole, a multiline buffer is a single line containing '\n'
         if self.index == 0 {
-            self.initial_line = LineBuffer::new();
-            self.initial_line.set_buffer(line.get_buffer().into());
-            self.initial_line
-                .set_insertion_point(line.line(), line.offset());
+            self.initial_line = line.clone();
         } else {
-            line.set_buffer(self.initial_line.get_buffer().into());
-            line.set_insertion_point(self.initial_line.line(), self.initial_line.offset())
+            *line = self.initial_line.clone();
         }
-        let completions = self
+        let suggestions = self
             .completer
-            .complete(self.initial_line.get_buffer(), self.initial_line.offset());
-        if !completions.is_empty() {
+            .suggest(self.initial_line.get_buffer(), self.initial_line.offset());
+        if !suggestions.is_empty() {
             match self.index {
-                index if index < completions.len() => {
+                index if index < suggestions.len() => {
                     self.index += 1;
-                    let span = completions[index].0;
-                    let mut offset = line.offset();
-                    offset += completions[index].1.len() - (span.end - span.start);
-
-                    // TODO improve the support for multiline replace
-                    line.replace(span.start..span.end, 0, &completions[index].1);
-                    line.set_insertion_point(line.line(), offset);
+                    apply_suggestion(line, &suggestions[index]);
                 }
                 _ => {
                     self.reset_index();
@@ -140,6 +157,176 @@ pub trait Completer {
     fn complete(&self, line: &str, pos: usize) -> Vec<(Span, String)>;
 }
 
+/// The kind of item a suggestion completes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SuggestionKind {
+    /// A command, builtin or external
+    Command,
+
+    /// A flag of a command
+    Flag,
+
+    /// A variable
+    Variable,
+
+    /// A file
+    File,
+
+    /// A directory
+    Directory,
+
+    /// Anything else
+    Other,
+}
+
+/// A completion candidate with the metadata to show it in a menu or a hint
+///
+/// # Example
+/// ```
+/// use reedline::{Span, Suggestion, SuggestionKind};
+///
+/// let suggestion = Suggestion::new("where", Span::new(0, 2))
+///     .with_description("Filter values based on a condition.")
+///     .with_kind(SuggestionKind::Command)
+///     .with_append_whitespace(true);
+///
+/// assert_eq!(suggestion.display_value(), "where");
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    /// The value inserted in place of the span
+    pub value: String,
+
+    /// The text shown for the suggestion if it differs from the inserted value
+    pub display: Option<String>,
+
+    /// A description shown next to the suggestion, like a command's usage line
+    pub description: Option<String>,
+
+    /// The kind of item the suggestion completes
+    pub kind: Option<SuggestionKind>,
+
+    /// The span of the line replaced by the value
+    pub span: Span,
+
+    /// Whether a space is inserted after the value, so the next word can be typed right away
+    pub append_whitespace: bool,
+}
+
+impl Suggestion {
+    /// Create a suggestion replacing `span` with `value`
+    pub fn new(value: impl Into<String>, span: Span) -> Suggestion {
+        Suggestion {
+            value: value.into(),
+            span,
+            ..Suggestion::default()
+        }
+    }
+
+    /// A builder that sets the text shown instead of the value
+    pub fn with_display(mut self, display: impl Into<String>) -> Suggestion {
+        self.display = Some(display.into());
+        self
+    }
+
+    /// A builder that sets the description shown next to the suggestion
+    pub fn with_description(mut self, description: impl Into<String>) -> Suggestion {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// A builder that sets the kind of item the suggestion completes
+    pub fn with_kind(mut self, kind: SuggestionKind) -> Suggestion {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// A builder that configures whether a space is inserted after the value
+    pub fn with_append_whitespace(mut self, append_whitespace: bool) -> Suggestion {
+        self.append_whitespace = append_whitespace;
+        self
+    }
+
+    /// The text to show for the suggestion, the display text or else the value
+    pub fn display_value(&self) -> &str {
+        self.display.as_deref().unwrap_or(&self.value)
+    }
+}
+
+impl From<(Span, String)> for Suggestion {
+    fn from((span, value): (Span, String)) -> Self {
+        Suggestion::new(value, span)
+    }
+}
+
+/// A trait that converts a line and position to [`Suggestion`]s, completions with metadata like
+/// a description and the kind of item they complete.
+///
+/// A [`Completer`] can be used where a `SuggestionCompleter` is expected with a
+/// [`CompleterAdapter`].
+pub trait SuggestionCompleter {
+    /// The suggestions for the given line and position
+    fn suggest(&self, line: &str, pos: usize) -> Vec<Suggestion>;
+
+    /// Whether the suggestions for the last line asked for are still being computed, the editor
+    /// asks again shortly to paint them once they arrive
+    fn is_pending(&self) -> bool {
+        false
+    }
+}
+
+/// Adapts a [`Completer`] returning `(Span, String)` pairs to a [`SuggestionCompleter`]
+///
+/// The adapter is [`Send`] if the completer is, e.g. when it is a `Box<dyn Completer + Send>`.
+///
+/// # Example
+/// ```
+/// use reedline::{CompleterAdapter, DefaultCompleter, Span, Suggestion, SuggestionCompleter};
+///
+/// let completer = DefaultCompleter::new(vec!["test".into()]);
+/// let adapter = CompleterAdapter::new(Box::new(completer));
+///
+/// assert_eq!(adapter.suggest("te", 2), vec![Suggestion::new("test", Span::new(0, 2))]);
+/// ```
+pub struct CompleterAdapter<C: Completer + ?Sized = dyn Completer> {
+    completer: Box<C>,
+}
+
+impl<C: Completer + ?Sized> CompleterAdapter<C> {
+    /// Wrap a completer
+    pub fn new(completer: Box<C>) -> CompleterAdapter<C> {
+        CompleterAdapter { completer }
+    }
+}
+
+impl<C: Completer + ?Sized> SuggestionCompleter for CompleterAdapter<C> {
+    fn suggest(&self, line: &str, pos: usize) -> Vec<Suggestion> {
+        self.completer
+            .complete(line, pos)
+            .into_iter()
+            .map(Suggestion::from)
+            .collect()
+    }
+}
+
+/// Replace the span of the suggestion with its value and move the insertion point behind it
+pub(crate) fn apply_suggestion(line: &mut LineBuffer, suggestion: &Suggestion) {
+    let span = suggestion.span;
+    let mut value = suggestion.value.clone();
+    let followed_by_space = line
+        .get_buffer()
+        .get(span.end..)
+        .unwrap_or("")
+        .starts_with(' ');
+    if suggestion.append_whitespace && !followed_by_space {
+        value.push(' ');
+    }
+
+    // TODO improve the support for multiline replace
+    line.replace(span.start..span.end, 0, &value);
+    line.set_insertion_point(line.line(), span.start + value.len());
+}
+
 /// A default completer that can detect keywords
 /// # Example
 /// # Example
@@ -159,17 +346,21 @@ pub trait Completer {
 #[derive(Debug, Clone)]
 pub struct DefaultCompleter {
     root: CompletionNode,
-    inclusions: Rc<BTreeSet<char>>,
+    inclusions: Arc<BTreeSet<char>>,
     min_word_len: usize,
+    fuzzy: bool,
+    ignore_case: bool,
 }
 
 impl Default for DefaultCompleter {
     fn default() -> Self {
-        let inclusions = Rc::new(BTreeSet::new());
+        let inclusions = Arc::new(BTreeSet::new());
         Self {
             root: CompletionNode::new(inclusions.clone()),
             inclusions,
             min_word_len: 2,
+            fuzzy: false,
+            ignore_case: false,
         }
     }
 }
@@ -205,6 +396,10 @@ impl Completer for DefaultCompleter {
     ///     ]);
     /// ```
     fn complete(&self, line: &str, pos: usize) -> Vec<(Span, String)> {
+        if self.fuzzy || self.ignore_case {
+            return self.complete_scored(line, pos);
+        }
+
         let mut span_line_whitespaces = 0;
         let mut completions = vec![];
         if !line.is_empty() {
@@ -320,7 +515,7 @@ impl DefaultCompleter {
         incl.iter().for_each(|c| {
             set.insert(*c);
         });
-        let inclusions = Rc::new(set);
+        let inclusions = Arc::new(set);
         Self {
             root: CompletionNode::new(inclusions.clone()),
             inclusions,
@@ -400,17 +595,114 @@ impl DefaultCompleter {
         self.min_word_len = len;
         self
     }
+
+    /// A builder that matches the words as subsequences instead of by prefix. The completions
+    /// are ranked by how well they match, consecutive characters and characters at the start of
+    /// a word rank higher.
+    ///
+    /// # Example
+    /// ```
+    /// use reedline::{DefaultCompleter,Completer,Span};
+    ///
+    /// let mut completions = DefaultCompleter::default().with_fuzzy_match();
+    /// completions.insert(vec!["gecko","git checkout","git commit"].iter().map(|s| s.to_string()).collect());
+    /// assert_eq!(
+    ///     completions.complete("gco",3),
+    ///     vec![
+    ///         (Span { start: 0, end: 3 }, "git commit".into()),
+    ///         (Span { start: 0, end: 3 }, "git checkout".into()),
+    ///         (Span { start: 0, end: 3 }, "gecko".into()),
+    ///     ]);
+    /// assert_eq!(
+    ///     completions.complete("gc",2),
+    ///     vec![
+    ///         (Span { start: 0, end: 2 }, "git checkout".into()),
+    ///         (Span { start: 0, end: 2 }, "git commit".into()),
+    ///         (Span { start: 0, end: 2 }, "gecko".into()),
+    ///     ]);
+    /// ```
+    pub fn with_fuzzy_match(mut self) -> DefaultCompleter {
+        self.fuzzy = true;
+        self
+    }
+
+    /// A builder that ignores the case of the typed text and the words when matching
+    ///
+    /// # Example
+    /// ```
+    /// use reedline::{DefaultCompleter,Completer,Span};
+    ///
+    /// let mut completions = DefaultCompleter::default().with_ignore_case();
+    /// completions.insert(vec!["Batman","batmobile","robin"].iter().map(|s| s.to_string()).collect());
+    /// assert_eq!(
+    ///     completions.complete("BAT",3),
+    ///     vec![
+    ///         (Span { start: 0, end: 3 }, "Batman".into()),
+    ///         (Span { start: 0, end: 3 }, "batmobile".into()),
+    ///     ]);
+    /// ```
+    pub fn with_ignore_case(mut self) -> DefaultCompleter {
+        self.ignore_case = true;
+        self
+    }
+
+    // Match every word against the typed text and rank them, for the fuzzy and case insensitive
+    // modes the trie can't serve
+    fn complete_scored(&self, line: &str, pos: usize) -> Vec<(Span, String)> {
+        let typed = &line[..pos];
+
+        // The typed text a word can complete, from the start of every word of the line, the
+        // longest one first
+        let starts: Vec<usize> = typed
+            .char_indices()
+            .filter(|(index, c)| *c != ' ' && (*index == 0 || typed[..*index].ends_with(' ')))
+            .map(|(index, _)| index)
+            .collect();
+
+        let mut completions: Vec<(i32, Span, String)> = self
+            .root
+            .collect(String::new())
+            .into_iter()
+            .filter_map(|word| {
+                starts.iter().find_map(|start| {
+                    let query = &typed[*start..];
+                    if query == word {
+                        return None;
+                    }
+
+                    self.score(query, &word)
+                        .map(|score| (score, Span::new(*start, pos), word.clone()))
+                })
+            })
+            .collect();
+
+        completions.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.2.cmp(&b.2)));
+        completions
+            .into_iter()
+            .map(|(_, span, word)| (span, word))
+            .collect()
+    }
+
+    fn score(&self, query: &str, word: &str) -> Option<i32> {
+        if self.fuzzy {
+            fuzzy_score(query, word, self.ignore_case)
+        } else if word.to_lowercase().starts_with(&query.to_lowercase()) {
+            Some(0)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 struct CompletionNode {
     subnodes: BTreeMap<char, CompletionNode>,
     leaf: bool,
-    inclusions: Rc<BTreeSet<char>>,
+    inclusions: Arc<BTreeSet<char>>,
 }
 
 impl CompletionNode {
-    fn new(incl: Rc<BTreeSet<char>>) -> Self {
+    fn new(incl: Arc<BTreeSet<char>>) -> Self {
         Self {
             subnodes: BTreeMap::new(),
             leaf: false,
@@ -0,0 +1,222 @@
+use {
+    crate::{Completer, CompleterAdapter, Span, Suggestion, SuggestionCompleter},
+    std::{cmp::Reverse, collections::HashSet},
+};
+
+/// Decides from the word before the cursor whether a source is consulted
+type Trigger = Box<dyn Fn(&str) -> bool + Send>;
+
+/// A completer that merges the suggestions of several sources
+///
+/// The sources are consulted from the highest priority to the lowest, sources of the same
+/// priority in the order they were added. A suggestion offered by more than one source is only
+/// kept from the first of them, and each source can be limited in how many suggestions it
+/// contributes and restricted to the words it is meant for with a trigger.
+///
+/// Sources and triggers are [`Send`], so a chain of slow sources can be moved to a
+/// [`BackgroundCompleter`](crate::BackgroundCompleter) as a whole.
+///
+/// # Example
+/// ```
+/// use reedline::{CompleterChain, CompletionSource, DefaultCompleter, SuggestionCompleter};
+///
+/// let commands = DefaultCompleter::new(vec!["ls".into(), "let".into()]);
+/// let mut variables = DefaultCompleter::with_inclusions(&['$']);
+/// variables.insert(vec!["$nu".into(), "$env".into()]);
+///
+/// let completer = CompleterChain::new()
+///     .with_source(CompletionSource::new(Box::new(commands)).with_priority(1))
+///     .with_source(
+///         CompletionSource::new(Box::new(variables))
+///             .with_trigger(|word: &str| word.starts_with('$')),
+///     );
+///
+/// assert_eq!(completer.suggest("l", 1).len(), 2);
+/// assert_eq!(completer.suggest("echo $n", 7)[0].value, "$nu");
+/// ```
+#[derive(Default)]
+pub struct CompleterChain {
+    sources: Vec<CompletionSource>,
+}
+
+/// A source of suggestions for a [`CompleterChain`]
+pub struct CompletionSource {
+    completer: Box<dyn SuggestionCompleter + Send>,
+    priority: i32,
+    limit: Option<usize>,
+    trigger: Option<Trigger>,
+}
+
+impl CompletionSource {
+    /// A source backed by a completer returning `(Span, String)` pairs
+    pub fn new(completer: Box<dyn Completer + Send>) -> CompletionSource {
+        CompletionSource::from_suggestion_completer(Box::new(CompleterAdapter::new(completer)))
+    }
+
+    /// A source backed by a completer returning [`Suggestion`]s
+    pub fn from_suggestion_completer(
+        completer: Box<dyn SuggestionCompleter + Send>,
+    ) -> CompletionSource {
+        CompletionSource {
+            completer,
+            priority: 0,
+            limit: None,
+            trigger: None,
+        }
+    }
+
+    /// A builder that sets the priority of the source, higher priorities are listed first.
+    /// The default priority is 0
+    pub fn with_priority(mut self, priority: i32) -> CompletionSource {
+        self.priority = priority;
+        self
+    }
+
+    /// A builder that limits how many suggestions the source contributes
+    pub fn with_limit(mut self, limit: usize) -> CompletionSource {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// A builder that only consults the source when the trigger accepts the word before the
+    /// cursor, like words starting with `$` for variables
+    pub fn with_trigger(
+        mut self,
+        trigger: impl Fn(&str) -> bool + Send + 'static,
+    ) -> CompletionSource {
+        self.trigger = Some(Box::new(trigger));
+        self
+    }
+
+    fn is_triggered(&self, word: &str) -> bool {
+        match &self.trigger {
+            Some(trigger) => trigger(word),
+            None => true,
+        }
+    }
+}
+
+impl CompleterChain {
+    /// Create a chain without any sources
+    pub fn new() -> CompleterChain {
+        CompleterChain::default()
+    }
+
+    /// A builder that adds a source of suggestions
+    pub fn with_source(mut self, source: CompletionSource) -> CompleterChain {
+        // Keep the sources ordered by priority, the sort is stable so ties stay in order
+        self.sources.push(source);
+        self.sources.sort_by_key(|source| Reverse(source.priority));
+        self
+    }
+}
+
+impl SuggestionCompleter for CompleterChain {
+    fn suggest(&self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let word = line[..pos]
+            .rsplit(char::is_whitespace)
+            .next()
+            .unwrap_or_default();
+
+        let mut suggestions: Vec<Suggestion> = vec![];
+        // The span and value of the suggestions so far, to drop the ones offered again
+        let mut known = HashSet::new();
+        for source in self
+            .sources
+            .iter()
+            .filter(|source| source.is_triggered(word))
+        {
+            let new_suggestions: Vec<Suggestion> = source
+                .completer
+                .suggest(line, pos)
+                .into_iter()
+                .filter(|suggestion| !known.contains(&(suggestion.span, suggestion.value.clone())))
+                .take(source.limit.unwrap_or(usize::MAX))
+                .collect();
+
+            known.extend(
+                new_suggestions
+                    .iter()
+                    .map(|suggestion| (suggestion.span, suggestion.value.clone())),
+            );
+            suggestions.extend(new_suggestions);
+        }
+
+        suggestions
+    }
+
+    fn is_pending(&self) -> bool {
+        self.sources
+            .iter()
+            .any(|source| source.completer.is_pending())
+    }
+}
+
+impl Completer for CompleterChain {
+    fn complete(&self, line: &str, pos: usize) -> Vec<(Span, String)> {
+        self.suggest(line, pos)
+            .into_iter()
+            .map(|suggestion| (suggestion.span, suggestion.value))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DefaultCompleter;
+    use pretty_assertions::assert_eq;
+
+    fn source(words: &[&str]) -> CompletionSource {
+        let mut completer = DefaultCompleter::with_inclusions(&['$']);
+        completer.insert(words.iter().map(|word| word.to_string()).collect());
+
+        CompletionSource::new(Box::new(completer))
+    }
+
+    fn values(chain: &CompleterChain, line: &str) -> Vec<String> {
+        chain
+            .suggest(line, line.len())
+            .into_iter()
+            .map(|suggestion| suggestion.value)
+            .collect()
+    }
+
+    #[test]
+    fn sources_are_merged_by_priority_without_duplicates() {
+        let chain = CompleterChain::new()
+            .with_source(source(&["hello", "help"]))
+            .with_source(source(&["help", "helm"]).with_priority(1));
+
+        assert_eq!(values(&chain, "hel"), vec!["helm", "help", "hello"]);
+    }
+
+    #[test]
+    fn sources_contribute_up_to_their_limit() {
+        let chain = CompleterChain::new()
+            .with_source(source(&["hello", "help", "helm"]).with_limit(2))
+            .with_source(source(&["helix"]));
+
+        assert_eq!(values(&chain, "hel"), vec!["hello", "helm", "helix"]);
+    }
+
+    #[test]
+    fn sources_are_only_consulted_when_triggered() {
+        let chain = CompleterChain::new()
+            .with_source(source(&["$env", "$nu"]).with_trigger(|word| word.starts_with('$')))
+            .with_source(source(&["echo", "$nothing"]));
+
+        assert_eq!(values(&chain, "echo $n"), vec!["$nu", "$nothing"]);
+        assert_eq!(values(&chain, "ec"), vec!["echo"]);
+    }
+
+    #[test]
+    fn chains_can_run_in_the_background() {
+        let chain = CompleterChain::new()
+            .with_source(source(&["hello"]).with_trigger(|word| word.starts_with('h')));
+        let completer = crate::BackgroundCompleter::new(Box::new(chain))
+            .with_budget(std::time::Duration::from_secs(5));
+
+        assert_eq!(completer.suggest("he", 2)[0].value, "hello");
+    }
+}
@@ -0,0 +1,625 @@
+use {
+    crate::{
+        completer::{apply_suggestion, ComplationActionHandler},
+        line_buffer::LineBuffer,
+        Completer, CompleterAdapter, DefaultCompleter, Suggestion, SuggestionCompleter,
+        SuggestionKind,
+    },
+    crossterm::event::{KeyCode, KeyModifiers},
+    nu_ansi_term::Style,
+    unicode_width::UnicodeWidthStr,
+};
+
+/// The default number of rows the completion menu takes below the buffer
+pub static DEFAULT_MENU_MAX_ROWS: u16 = 10;
+
+// Spacing between the columns of the menu
+const COLUMN_PADDING: usize = 2;
+
+/// The candidates of a completion, laid out in columns below the buffer with one of them selected
+///
+/// Candidates flow from left to right and then down. When any of them has a description, the
+/// candidates are listed in a single column with their descriptions next to them. When they
+/// need more rows than the menu is allowed to take, the menu shows the page holding the
+/// selected candidate and a status row.
+#[derive(Debug, Clone)]
+pub struct CompletionMenu {
+    suggestions: Vec<Suggestion>,
+    selected: usize,
+    max_rows: u16,
+    // Layout for the last known screen size
+    screen_width: usize,
+    columns: usize,
+    column_width: usize,
+    page_rows: usize,
+}
+
+impl CompletionMenu {
+    /// Create a menu for the given suggestions with the first one selected
+    pub fn new(suggestions: Vec<Suggestion>) -> CompletionMenu {
+        let page_rows = suggestions.len();
+
+        CompletionMenu {
+            suggestions,
+            selected: 0,
+            max_rows: DEFAULT_MENU_MAX_ROWS,
+            screen_width: 0,
+            columns: 1,
+            column_width: 0,
+            page_rows,
+        }
+    }
+
+    /// A builder that limits the rows the menu takes, including its status row
+    pub fn with_max_rows(mut self, max_rows: u16) -> CompletionMenu {
+        self.max_rows = max_rows;
+        self
+    }
+
+    /// The index of the selected candidate
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// The selected suggestion
+    pub fn selected_suggestion(&self) -> Option<&Suggestion> {
+        self.suggestions.get(self.selected)
+    }
+
+    /// Arrange the candidates for the screen width and the rows available below the buffer.
+    ///
+    /// Returns the number of rows the menu will take when painted.
+    pub fn layout(&mut self, screen_width: u16, available_rows: u16) -> u16 {
+        self.screen_width = usize::from(screen_width).max(1);
+        let max_rows = usize::from(self.max_rows.min(available_rows)).max(1);
+
+        let widest = self
+            .suggestions
+            .iter()
+            .map(|suggestion| suggestion.display_value().width())
+            .max()
+            .unwrap_or(0);
+        self.column_width = (widest + COLUMN_PADDING).min(self.screen_width);
+        self.columns = if self.has_descriptions() {
+            1
+        } else {
+            (self.screen_width / self.column_width.max(1)).max(1)
+        };
+
+        let rows = self.total_rows();
+        if rows <= max_rows {
+            self.page_rows = rows;
+            rows as u16
+        } else {
+            // Keep a row for the page status
+            self.page_rows = (max_rows - 1).max(1);
+            (self.page_rows + 1) as u16
+        }
+    }
+
+    /// The rows of the page holding the selected candidate, ready to be painted
+    pub fn lines(&self) -> Vec<String> {
+        let first_row = self.selected / self.columns / self.page_rows * self.page_rows;
+        let last_row = (first_row + self.page_rows).min(self.total_rows());
+
+        let mut lines: Vec<String> = (first_row..last_row)
+            .map(|row| {
+                let start = row * self.columns;
+                let end = (start + self.columns).min(self.suggestions.len());
+                (start..end)
+                    .map(|index| self.cell(index))
+                    .collect::<String>()
+            })
+            .collect();
+
+        if self.page_rows < self.total_rows() {
+            let first = first_row * self.columns + 1;
+            let last = (last_row * self.columns).min(self.suggestions.len());
+            lines.push(
+                Style::new()
+                    .dimmed()
+                    .paint(format!(
+                        "-- {}-{} of {} --",
+                        first,
+                        last,
+                        self.suggestions.len()
+                    ))
+                    .to_string(),
+            );
+        }
+
+        lines
+    }
+
+    /// Select the next candidate, wrapping around to the first one
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.suggestions.len().max(1);
+    }
+
+    /// Select the previous candidate, wrapping around to the last one
+    pub fn select_previous(&mut self) {
+        self.selected = match self.selected {
+            0 => self.suggestions.len().saturating_sub(1),
+            selected => selected - 1,
+        };
+    }
+
+    /// Select the candidate in the row below, wrapping around to the top of the column
+    pub fn select_down(&mut self) {
+        if self.selected + self.columns < self.suggestions.len() {
+            self.selected += self.columns;
+        } else {
+            self.selected %= self.columns;
+        }
+    }
+
+    /// Select the candidate in the row above, wrapping around to the bottom of the column
+    pub fn select_up(&mut self) {
+        if self.selected >= self.columns {
+            self.selected -= self.columns;
+        } else {
+            let last_row = self.suggestions.len().saturating_sub(1) / self.columns;
+            let mut selected = last_row * self.columns + self.selected;
+            if selected >= self.suggestions.len() {
+                selected = selected.saturating_sub(self.columns);
+            }
+            self.selected = selected;
+        }
+    }
+
+    /// Select the candidate one page further down
+    pub fn select_next_page(&mut self) {
+        let page = self.page_rows * self.columns;
+        self.selected = (self.selected + page).min(self.suggestions.len().saturating_sub(1));
+    }
+
+    /// Select the candidate one page further up
+    pub fn select_previous_page(&mut self) {
+        let page = self.page_rows * self.columns;
+        self.selected = self.selected.saturating_sub(page);
+    }
+
+    fn has_descriptions(&self) -> bool {
+        self.suggestions
+            .iter()
+            .any(|suggestion| suggestion.description.is_some())
+    }
+
+    fn total_rows(&self) -> usize {
+        let full_rows = self.suggestions.len() / self.columns;
+        if full_rows * self.columns < self.suggestions.len() {
+            full_rows + 1
+        } else {
+            full_rows
+        }
+    }
+
+    // A candidate padded to the column width and followed by its description, the selected
+    // one is highlighted
+    fn cell(&self, index: usize) -> String {
+        let suggestion = &self.suggestions[index];
+        let item = truncate(
+            suggestion.display_value(),
+            self.column_width.saturating_sub(1),
+        );
+        let padding = " ".repeat(self.column_width - item.width());
+
+        let mut cell = if index == self.selected {
+            format!("{}{}", Style::new().reverse().paint(item), padding)
+        } else {
+            format!("{}{}", item, padding)
+        };
+
+        if let Some(description) = &suggestion.description {
+            let description = truncate(
+                description,
+                self.screen_width.saturating_sub(self.column_width),
+            );
+            cell.push_str(&Style::new().dimmed().paint(description).to_string());
+        }
+
+        cell
+    }
+}
+
+// The longest prefix of `text` that fits in `width` columns
+fn truncate(text: &str, width: usize) -> String {
+    let mut truncated = String::new();
+    for c in text.chars() {
+        if truncated.width() + c.to_string().width() > width {
+            break;
+        }
+        truncated.push(c);
+    }
+
+    truncated
+}
+
+/// A handler that shows the candidates of the Completer in a menu below the buffer
+///
+/// The first Tab completes a single candidate right away and opens the menu when there are
+/// more. While the menu is shown, Tab, Shift-Tab and the arrow keys move the selection,
+/// PageUp and PageDown move a page at a time, Enter accepts the selected candidate and Esc
+/// restores the line from before the completion. The selected candidate is previewed in the
+/// buffer and any other key accepts it and continues editing.
+///
+/// With [`MenuCompletionActionHandler::with_common_prefix`] it behaves like bash and zsh
+/// instead: the first Tab only extends the buffer to the longest prefix the candidates share,
+/// the next one opens the menu, and a single candidate is inserted with a trailing space.
+///
+/// # Example
+/// ```rust,no_run
+/// use reedline::{DefaultCompleter, MenuCompletionActionHandler, Reedline};
+///
+/// let commands = vec!["test".into(), "hello world".into(), "hello world reedline".into()];
+/// let completer = Box::new(DefaultCompleter::new_with_wordlen(commands, 2));
+///
+/// let mut line_editor = Reedline::new().with_completion_action_handler(Box::new(
+///     MenuCompletionActionHandler::default().with_completer(completer),
+/// ));
+/// ```
+pub struct MenuCompletionActionHandler {
+    completer: Box<dyn SuggestionCompleter>,
+    initial_line: LineBuffer,
+    menu: Option<CompletionMenu>,
+    max_rows: u16,
+    common_prefix: bool,
+}
+
+impl Default for MenuCompletionActionHandler {
+    fn default() -> Self {
+        MenuCompletionActionHandler {
+            completer: Box::new(CompleterAdapter::new(Box::new(DefaultCompleter::default()))),
+            initial_line: LineBuffer::new(),
+            menu: None,
+            max_rows: DEFAULT_MENU_MAX_ROWS,
+            common_prefix: false,
+        }
+    }
+}
+
+impl MenuCompletionActionHandler {
+    /// A builder that configures the completer providing the candidates
+    pub fn with_completer(mut self, completer: Box<dyn Completer>) -> MenuCompletionActionHandler {
+        self.completer = Box::new(CompleterAdapter::new(completer));
+        self
+    }
+
+    /// A builder that configures a completer providing [`Suggestion`]s, their descriptions are
+    /// shown in the menu
+    pub fn with_suggestion_completer(
+        mut self,
+        completer: Box<dyn SuggestionCompleter>,
+    ) -> MenuCompletionActionHandler {
+        self.completer = completer;
+        self
+    }
+
+    /// A builder that limits the rows the menu takes below the buffer
+    pub fn with_max_rows(mut self, max_rows: u16) -> MenuCompletionActionHandler {
+        self.max_rows = max_rows;
+        self
+    }
+
+    /// A builder that completes the longest common prefix of the candidates before opening the
+    /// menu, the way bash and zsh do
+    pub fn with_common_prefix(mut self) -> MenuCompletionActionHandler {
+        self.common_prefix = true;
+        self
+    }
+
+    // Apply the suggestion to the line from before the completion
+    fn apply(&self, line: &mut LineBuffer, suggestion: &Suggestion) {
+        *line = self.initial_line.clone();
+
+        apply_suggestion(line, suggestion);
+    }
+
+    // Extend the typed text to the prefix all suggestions share, returns whether it grew
+    fn extend_to_common_prefix(&self, line: &mut LineBuffer, suggestions: &[Suggestion]) -> bool {
+        let span = suggestions[0].span;
+        if suggestions.iter().any(|suggestion| suggestion.span != span) {
+            return false;
+        }
+
+        let prefix = suggestions[1..]
+            .iter()
+            .fold(suggestions[0].value.as_str(), |prefix, suggestion| {
+                common_prefix(prefix, &suggestion.value)
+            });
+
+        // Fuzzy matches may share a prefix that doesn't start with the typed text
+        let typed = &self.initial_line.get_buffer()[span.start..span.end];
+        if prefix.len() <= typed.len() || !prefix.to_lowercase().starts_with(&typed.to_lowercase())
+        {
+            return false;
+        }
+
+        self.apply(line, &Suggestion::new(prefix, span));
+        true
+    }
+}
+
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let end = a
+        .char_indices()
+        .zip(b.chars())
+        .find(|((_, a), b)| a != b)
+        .map(|((index, _), _)| index)
+        .unwrap_or_else(|| a.len().min(b.len()));
+
+    &a[..end]
+}
+
+impl ComplationActionHandler for MenuCompletionActionHandler {
+    fn handle(&mut self, line: &mut LineBuffer) {
+        if self.menu.is_some() {
+            self.handle_key(line, KeyModifiers::NONE, KeyCode::Tab);
+            return;
+        }
+
+        // Copied as a whole, a multiline buffer is a single line containing '\n'
+        self.initial_line = line.clone();
+
+        let mut suggestions = self
+            .completer
+            .suggest(self.initial_line.get_buffer(), self.initial_line.offset());
+
+        match suggestions.len() {
+            0 => {}
+            1 => {
+                let mut suggestion = suggestions.remove(0);
+                // Directories are left open, so the next Tab completes inside of them
+                if self.common_prefix && suggestion.kind != Some(SuggestionKind::Directory) {
+                    suggestion.append_whitespace = true;
+                }
+                self.apply(line, &suggestion);
+            }
+            _ if self.common_prefix && self.extend_to_common_prefix(line, &suggestions) => {}
+            _ => {
+                let menu = CompletionMenu::new(suggestions).with_max_rows(self.max_rows);
+                if let Some(suggestion) = menu.selected_suggestion() {
+                    self.apply(line, suggestion);
+                }
+                self.menu = Some(menu);
+            }
+        }
+    }
+
+    fn reset_index(&mut self) {
+        self.menu = None;
+    }
+
+    fn handle_key(
+        &mut self,
+        line: &mut LineBuffer,
+        modifiers: KeyModifiers,
+        code: KeyCode,
+    ) -> bool {
+        let mut menu = match self.menu.take() {
+            Some(menu) => menu,
+            None => return false,
+        };
+
+        match (modifiers, code) {
+            (KeyModifiers::NONE, KeyCode::Tab) | (KeyModifiers::NONE, KeyCode::Right) => {
+                menu.select_next()
+            }
+            (_, KeyCode::BackTab) | (KeyModifiers::NONE, KeyCode::Left) => menu.select_previous(),
+            (KeyModifiers::NONE, KeyCode::Down) => menu.select_down(),
+            (KeyModifiers::NONE, KeyCode::Up) => menu.select_up(),
+            (KeyModifiers::NONE, KeyCode::PageDown) => menu.select_next_page(),
+            (KeyModifiers::NONE, KeyCode::PageUp) => menu.select_previous_page(),
+            // Keep the previewed selection
+            (KeyModifiers::NONE, KeyCode::Enter) => return true,
+            (KeyModifiers::NONE, KeyCode::Esc) => {
+                *line = self.initial_line.clone();
+                return true;
+            }
+            _ => {
+                self.menu = Some(menu);
+                return false;
+            }
+        }
+
+        if let Some(suggestion) = menu.selected_suggestion() {
+            self.apply(line, suggestion);
+        }
+        self.menu = Some(menu);
+
+        true
+    }
+
+    fn menu(&mut self) -> Option<&mut CompletionMenu> {
+        self.menu.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Span;
+    use pretty_assertions::assert_eq;
+
+    fn menu(count: usize) -> CompletionMenu {
+        CompletionMenu::new(
+            (0..count)
+                .map(|i| Suggestion::new(format!("item{:02}", i), Span::default()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn candidates_are_laid_out_in_columns() {
+        let mut menu = menu(5);
+
+        // "itemNN" is 6 wide, so each column takes 8 and 20 columns fit 2 of them
+        assert_eq!(menu.layout(20, 10), 3);
+        assert_eq!(menu.lines().len(), 3);
+        assert_eq!(
+            menu.lines()[1..],
+            ["item02  item03  ".to_string(), "item04  ".to_string()]
+        );
+    }
+
+    #[test]
+    fn descriptions_are_listed_next_to_the_candidates() {
+        let mut menu = CompletionMenu::new(vec![
+            Suggestion::new("ls", Span::default()).with_description("List the files"),
+            Suggestion::new("where", Span::default())
+                .with_display("where <cond>")
+                .with_description("Filter values based on a condition"),
+        ]);
+
+        assert_eq!(menu.layout(30, 10), 2);
+        assert_eq!(
+            menu.lines()[1],
+            format!(
+                "where <cond>  {}",
+                Style::new().dimmed().paint("Filter values ba")
+            )
+        );
+    }
+
+    #[test]
+    fn navigation_moves_through_rows_and_columns() {
+        let mut menu = menu(5);
+        menu.layout(20, 10);
+
+        menu.select_down();
+        assert_eq!(menu.selected(), 2);
+        menu.select_next();
+        assert_eq!(menu.selected(), 3);
+        menu.select_down();
+        assert_eq!(menu.selected(), 1);
+        menu.select_up();
+        assert_eq!(menu.selected(), 3);
+        menu.select_previous();
+        menu.select_previous();
+        menu.select_previous();
+        menu.select_previous();
+        assert_eq!(menu.selected(), 4);
+    }
+
+    #[test]
+    fn candidates_beyond_the_available_rows_are_paged() {
+        let mut menu = menu(10);
+
+        // One candidate per row, 3 rows of candidates and the status row
+        assert_eq!(menu.layout(10, 4), 4);
+        assert_eq!(menu.lines().len(), 4);
+        assert!(menu.lines()[3].contains("-- 1-3 of 10 --"));
+
+        menu.select_next_page();
+        assert_eq!(menu.selected(), 3);
+        assert!(menu.lines()[0].contains("item03"));
+        assert!(menu.lines()[3].contains("-- 4-6 of 10 --"));
+
+        menu.select_previous_page();
+        assert_eq!(menu.selected(), 0);
+    }
+
+    #[test]
+    fn handler_previews_and_accepts_the_selection() {
+        let completer = DefaultCompleter::new(vec!["hello".into(), "help".into()]);
+        let mut handler =
+            MenuCompletionActionHandler::default().with_completer(Box::new(completer));
+        let mut line = LineBuffer::new();
+        line.insert_str("he");
+
+        handler.handle(&mut line);
+        assert!(handler.menu().is_some());
+        assert_eq!(line.get_buffer(), "hello");
+
+        assert!(handler.handle_key(&mut line, KeyModifiers::NONE, KeyCode::Tab));
+        assert_eq!(line.get_buffer(), "help");
+        assert_eq!(line.offset(), 4);
+
+        assert!(handler.handle_key(&mut line, KeyModifiers::NONE, KeyCode::Enter));
+        assert!(handler.menu().is_none());
+        assert_eq!(line.get_buffer(), "help");
+        assert!(!handler.handle_key(&mut line, KeyModifiers::NONE, KeyCode::Tab));
+    }
+
+    #[test]
+    fn common_prefix_is_completed_before_the_menu_opens() {
+        let completer = DefaultCompleter::new(vec!["hello".into(), "help".into(), "world".into()]);
+        let mut handler = MenuCompletionActionHandler::default()
+            .with_completer(Box::new(completer))
+            .with_common_prefix();
+        let mut line = LineBuffer::new();
+        line.insert_str("he");
+
+        handler.handle(&mut line);
+        assert!(handler.menu().is_none());
+        assert_eq!(line.get_buffer(), "hel");
+
+        handler.reset_index();
+        handler.handle(&mut line);
+        assert!(handler.menu().is_some());
+        assert_eq!(line.get_buffer(), "hello");
+
+        handler.reset_index();
+        let mut line = LineBuffer::new();
+        line.insert_str("wo");
+        handler.handle(&mut line);
+        assert!(handler.menu().is_none());
+        assert_eq!(line.get_buffer(), "world ");
+        assert_eq!(line.offset(), 6);
+    }
+
+    #[test]
+    fn finds_the_common_prefix() {
+        assert_eq!(common_prefix("hello", "help"), "hel");
+        assert_eq!(common_prefix("hello", "hell"), "hell");
+        assert_eq!(common_prefix("über", "übel"), "übe");
+        assert_eq!(common_prefix("abc", "xyz"), "");
+    }
+
+    #[test]
+    fn escape_restores_the_line() {
+        let completer = DefaultCompleter::new(vec!["hello".into(), "help".into()]);
+        let mut handler =
+            MenuCompletionActionHandler::default().with_completer(Box::new(completer));
+        let mut line = LineBuffer::new();
+        line.insert_str("he");
+
+        handler.handle(&mut line);
+        assert!(handler.handle_key(&mut line, KeyModifiers::NONE, KeyCode::Esc));
+
+        assert!(handler.menu().is_none());
+        assert_eq!(line.get_buffer(), "he");
+    }
+
+    #[test]
+    fn tab_on_an_empty_buffer_keeps_it_intact() {
+        let completer = DefaultCompleter::new(vec!["hello".into(), "help".into()]);
+        let mut handler =
+            MenuCompletionActionHandler::default().with_completer(Box::new(completer));
+        let mut line = LineBuffer::new();
+
+        handler.handle(&mut line);
+        handler.handle_key(&mut line, KeyModifiers::NONE, KeyCode::Esc);
+
+        assert!(handler.menu().is_none());
+        assert_eq!(line.get_buffer(), "");
+    }
+
+    #[test]
+    fn multiline_buffers_are_completed_as_a_whole() {
+        let completer = DefaultCompleter::new(vec!["hello".into(), "help".into()]);
+        let mut handler =
+            MenuCompletionActionHandler::default().with_completer(Box::new(completer));
+        let mut line = LineBuffer::new();
+        line.insert_str("echo a\necho he");
+
+        handler.handle(&mut line);
+        assert_eq!(line.get_buffer(), "echo a\necho hello");
+        assert!(handler.handle_key(&mut line, KeyModifiers::NONE, KeyCode::Tab));
+        assert_eq!(line.get_buffer(), "echo a\necho help");
+        assert_eq!(line.offset(), 16);
+
+        assert!(handler.handle_key(&mut line, KeyModifiers::NONE, KeyCode::Esc));
+        assert_eq!(line.get_buffer(), "echo a\necho he");
+    }
+}
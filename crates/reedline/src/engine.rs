@@ -6,25 +6,32 @@ use {
         completer::{ComplationActionHandler, DefaultCompletionActionHandler},
         default_emacs_keybindings,
         hinter::{DefaultHinter, Hinter},
-        history::{FileBackedHistory, History, HistoryNavigationQuery},
+        history::{FileBackedHistory, History, HistoryMetadata, HistoryNavigationQuery},
+        history_search_panel::{HistorySearchPanel, SearchPanelEvent},
         keybindings::{default_vi_insert_keybindings, default_vi_normal_keybindings, Keybindings},
         line_buffer::LineBuffer,
         painter::Painter,
         prompt::{PromptEditMode, PromptHistorySearch, PromptHistorySearchStatus, PromptViMode},
-        DefaultHighlighter, EditCommand, EditMode, Highlighter, Prompt, Signal, ViEngine,
+        terminal::{CrosstermTerminal, Terminal},
+        validator::{ValidationResult, Validator},
+        DefaultHighlighter, EditCommand, EditMode, Highlighter, HistoryNavigationPolicy, Prompt,
+        Signal, ViEngine,
     },
     crossterm::{
-        cursor::position,
-        event::{poll, read, Event, KeyCode, KeyEvent, KeyModifiers},
-        terminal, Result,
+        event::{Event, KeyCode, KeyEvent, KeyModifiers},
+        Result,
     },
-    std::{collections::HashMap, io::stdout, time::Duration},
+    std::{collections::HashMap, time::Duration},
 };
 
+// How often to check for a hint that is computed in the background
+const PENDING_HINT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
 #[derive(Debug, PartialEq, Eq)]
 enum InputMode {
     Regular,
     HistorySearch,
+    HistorySearchPanel,
     HistoryTraversal,
 }
 
@@ -55,6 +62,11 @@ pub struct Reedline {
     // History
     history: Box<dyn History>,
     input_mode: InputMode,
+    history_navigation_policy: HistoryNavigationPolicy,
+    // Buffer when browsing the history started, restored when moving past the newest match
+    history_origin: LineBuffer,
+    // Whether Up and Down move between the lines of a multiline buffer before browsing
+    up_down_in_buffer_first: bool,
 
     // Stdout
     painter: Painter,
@@ -75,6 +87,12 @@ pub struct Reedline {
     vi_engine: ViEngine,
 
     tab_handler: Box<dyn ComplationActionHandler>,
+
+    // Decides if Enter submits the buffer or starts a new line
+    validator: Option<Box<dyn Validator>>,
+
+    // Interactive history search, replaces the reverse search if set
+    search_panel: Option<HistorySearchPanel>,
 }
 
 impl Default for Reedline {
@@ -90,7 +108,8 @@ impl Reedline {
         let cut_buffer = Box::new(get_default_clipboard());
         let buffer_highlighter = Box::new(DefaultHighlighter::default());
         let hinter = Box::new(DefaultHinter::default());
-        let painter = Painter::new(stdout(), buffer_highlighter, hinter);
+        let terminal = Box::new(CrosstermTerminal::new());
+        let painter = Painter::new(terminal, buffer_highlighter, hinter);
         let mut keybindings_hashmap = HashMap::new();
         keybindings_hashmap.insert(EditMode::Emacs, default_emacs_keybindings());
         keybindings_hashmap.insert(EditMode::ViInsert, default_vi_insert_keybindings());
@@ -101,6 +120,9 @@ impl Reedline {
             cut_buffer,
             history,
             input_mode: InputMode::Regular,
+            history_navigation_policy: HistoryNavigationPolicy::Automatic,
+            history_origin: LineBuffer::new(),
+            up_down_in_buffer_first: true,
             painter,
             keybindings: keybindings_hashmap,
             edit_mode: EditMode::Emacs,
@@ -108,6 +130,8 @@ impl Reedline {
             //partial_command: None,
             vi_engine: ViEngine::new(),
             tab_handler: Box::new(DefaultCompletionActionHandler::default()),
+            validator: None,
+            search_panel: None,
         }
     }
 
@@ -190,6 +214,37 @@ impl Reedline {
         self
     }
 
+    /// A builder that configures the validator for your instance of the Reedline engine
+    ///
+    /// If the validator reports the buffer as incomplete, `Enter` inserts a newline and
+    /// editing continues on the next line instead of returning the buffer.
+    /// # Example
+    /// ```rust,no_run
+    /// // Create a reedline object with multiline input support
+    ///
+    /// use reedline::{DefaultValidator, Reedline};
+    ///
+    /// let mut line_editor = Reedline::new().with_validator(Box::new(DefaultValidator));
+    /// ```
+    pub fn with_validator(mut self, validator: Box<dyn Validator>) -> Reedline {
+        self.validator = Some(validator);
+        self
+    }
+
+    /// A builder that replaces the reverse history search with an interactive panel listing
+    /// the best matches below the prompt
+    /// # Example
+    /// ```rust,no_run
+    /// use reedline::{HistorySearchMode, HistorySearchPanel, Reedline};
+    ///
+    /// let mut line_editor = Reedline::new()
+    ///     .with_history_search_panel(HistorySearchPanel::new(HistorySearchMode::Substring));
+    /// ```
+    pub fn with_history_search_panel(mut self, search_panel: HistorySearchPanel) -> Reedline {
+        self.search_panel = Some(search_panel);
+        self
+    }
+
     /// A builder which configures the history for your instance of the Reedline engine
     /// # Example
     /// ```rust,no_run
@@ -211,13 +266,84 @@ impl Reedline {
         Ok(self)
     }
 
+    /// A builder which configures which history entries Up and Down browse through
+    /// # Example
+    /// ```rust
+    /// // Only recall entries containing what has been typed so far
+    ///
+    /// use reedline::{HistoryNavigationPolicy, Reedline};
+    ///
+    /// let mut line_editor =
+    ///     Reedline::new().with_history_navigation_policy(HistoryNavigationPolicy::Substring);
+    /// ```
+    pub fn with_history_navigation_policy(mut self, policy: HistoryNavigationPolicy) -> Reedline {
+        self.history_navigation_policy = policy;
+        self
+    }
+
+    /// A builder which configures whether Up and Down first move between the lines of a
+    /// multiline buffer, and only browse the history from its first or last line. Enabled by
+    /// default, if disabled they always browse the history.
+    pub fn with_up_down_in_buffer_first(mut self, in_buffer_first: bool) -> Reedline {
+        self.up_down_in_buffer_first = in_buffer_first;
+        self
+    }
+
+    /// A builder which configures the terminal your instance of the Reedline engine reads
+    /// input events from and paints to
+    /// # Example
+    /// ```rust
+    /// // Create a reedline object that runs without a TTY
+    ///
+    /// use reedline::{Reedline, VirtualTerminal};
+    ///
+    /// let terminal = VirtualTerminal::new(80, 24);
+    /// let mut line_editor = Reedline::new().with_terminal(Box::new(terminal.clone()));
+    /// ```
+    pub fn with_terminal(mut self, terminal: Box<dyn Terminal>) -> Reedline {
+        self.painter.set_terminal(terminal);
+        self
+    }
+
     /// A builder which configures the keybindings for your instance of the Reedline engine
+    ///
+    /// The keybindings are used in Emacs mode, see
+    /// [`with_edit_mode_keybindings`](Reedline::with_edit_mode_keybindings) for the vi modes.
     pub fn with_keybindings(mut self, keybindings: Keybindings) -> Reedline {
         self.keybindings.insert(EditMode::Emacs, keybindings);
 
         self
     }
 
+    /// A builder which configures the keybindings of a single edit mode
+    ///
+    /// In [`EditMode::ViNormal`] unmodified characters are vi commands and can't be rebound.
+    ///
+    /// ```rust
+    /// use crossterm::event::{KeyCode, KeyModifiers};
+    /// use reedline::{default_vi_insert_keybindings, EditCommand, EditMode, Reedline};
+    ///
+    /// let mut keybindings = default_vi_insert_keybindings();
+    /// keybindings.add_binding(
+    ///     KeyModifiers::CONTROL,
+    ///     KeyCode::Char('e'),
+    ///     vec![EditCommand::MoveToEnd],
+    /// );
+    ///
+    /// let line_editor = Reedline::new()
+    ///     .with_edit_mode(EditMode::ViInsert)
+    ///     .with_edit_mode_keybindings(EditMode::ViInsert, keybindings);
+    /// ```
+    pub fn with_edit_mode_keybindings(
+        mut self,
+        edit_mode: EditMode,
+        keybindings: Keybindings,
+    ) -> Reedline {
+        self.keybindings.insert(edit_mode, keybindings);
+
+        self
+    }
+
     /// A builder which configures the edit mode for your instance of the Reedline engine
     pub fn with_edit_mode(mut self, edit_mode: EditMode) -> Reedline {
         self.edit_mode = edit_mode;
@@ -278,17 +404,54 @@ impl Reedline {
         Ok(())
     }
 
+    /// Attach context to the most recently entered command, like its exit status and how long
+    /// it ran, once the command finished.
+    ///
+    /// Fields left `None` are unchanged, histories that don't store metadata ignore it.
+    /// # Example
+    /// ```rust,no_run
+    /// use reedline::{HistoryMetadata, Reedline, Signal, DefaultPrompt};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let mut line_editor = Reedline::new();
+    /// if let Ok(Signal::Success(buffer)) = line_editor.read_line(&DefaultPrompt::default()) {
+    ///     let start = Instant::now();
+    ///     // run the command in buffer
+    ///     line_editor
+    ///         .update_last_history_metadata(HistoryMetadata {
+    ///             exit_status: Some(0),
+    ///             duration: Some(start.elapsed()),
+    ///             ..HistoryMetadata::default()
+    ///         })
+    ///         .expect("Error updating the history");
+    /// }
+    /// ```
+    pub fn update_last_history_metadata(
+        &mut self,
+        metadata: HistoryMetadata,
+    ) -> std::io::Result<()> {
+        self.history.update_last_metadata(metadata)
+    }
+
+    /// Errors the history ran into while writing in the background since the last call, like
+    /// a shared [`FileBackedHistory`](crate::FileBackedHistory) failing to sync its file or an
+    /// SQLite history failing to store an entry.
+    /// The host can report them, the history keeps working with the entries it has.
+    pub fn take_history_errors(&mut self) -> Vec<std::io::Error> {
+        self.history.take_errors()
+    }
+
     /// Wait for input and provide the user with a specified [`Prompt`].
     ///
     /// Returns a [`crossterm::Result`] in which the `Err` type is [`crossterm::ErrorKind`]
     /// to distinguish I/O errors and the `Ok` variant wraps a [`Signal`] which
     /// handles user inputs.
     pub fn read_line(&mut self, prompt: &dyn Prompt) -> Result<Signal> {
-        terminal::enable_raw_mode()?;
+        self.terminal().enable_raw_mode()?;
 
         let result = self.read_line_helper(prompt);
 
-        terminal::disable_raw_mode()?;
+        self.terminal().disable_raw_mode()?;
 
         result
     }
@@ -341,9 +504,12 @@ impl Reedline {
                 EditCommand::SearchHistory | EditCommand::Up | EditCommand::PreviousHistory => {
                     self.history.back();
                 }
-                EditCommand::Down | EditCommand::NextHistory => {
+                EditCommand::SearchHistoryForward
+                | EditCommand::Down
+                | EditCommand::NextHistory => {
                     self.history.forward();
-                    // Hacky way to ensure that we don't fall of into failed search going forward
+                    // Without a newer match the current one is the newest, stay on it instead of
+                    // falling off into a failed search
                     if self.history.string_at_cursor().is_none() {
                         self.history.back();
                     }
@@ -355,6 +521,11 @@ impl Reedline {
         }
     }
 
+    /// The [`Terminal`] used for input events and size/cursor queries
+    fn terminal(&mut self) -> &mut dyn Terminal {
+        self.painter.terminal()
+    }
+
     fn move_to_start(&mut self) {
         self.line_buffer.move_to_start()
     }
@@ -387,6 +558,18 @@ impl Reedline {
         self.line_buffer.delete_left_grapheme();
     }
 
+    /// Insert the hint, or only its next word, if the cursor is at the end of the buffer where
+    /// the hint is shown
+    fn accept_hint(&mut self, whole: bool) {
+        if self.line_buffer.offset() != self.insertion_line().len() {
+            return;
+        }
+
+        let hint = self.painter.current_hint();
+        let hint = if whole { &hint[..] } else { first_word(&hint) };
+        self.line_buffer.insert_str(hint);
+    }
+
     fn delete(&mut self) {
         self.line_buffer.delete_right_grapheme();
     }
@@ -405,7 +588,9 @@ impl Reedline {
 
     fn up_command(&mut self) {
         // If we're at the top, then:
-        if !self.line_buffer.get_buffer()[0..self.line_buffer.offset()].contains('\n') {
+        if !self.up_down_in_buffer_first
+            || !self.line_buffer.get_buffer()[0..self.line_buffer.offset()].contains('\n')
+        {
             // If we're at the top, move to previous history
             self.previous_history();
         } else {
@@ -443,7 +628,9 @@ impl Reedline {
 
     fn down_command(&mut self) {
         // If we're at the top, then:
-        if !self.line_buffer.get_buffer()[self.line_buffer.offset()..].contains('\n') {
+        if !self.up_down_in_buffer_first
+            || !self.line_buffer.get_buffer()[self.line_buffer.offset()..].contains('\n')
+        {
             // If we're at the top, move to previous history
             self.next_history();
         } else {
@@ -507,24 +694,76 @@ impl Reedline {
     }
 
     fn set_history_navigation_based_on_line_buffer(&mut self) {
-        if self.line_buffer.is_empty()
-            || self.line_buffer.offset() != self.line_buffer.get_buffer().len()
-        {
-            self.history
-                .set_navigation(HistoryNavigationQuery::Normal(self.line_buffer.clone()));
-        } else {
-            let buffer = self.insertion_line().to_string();
-            self.history
-                .set_navigation(HistoryNavigationQuery::PrefixSearch(buffer));
-        }
+        self.history_origin = self.line_buffer.clone();
+        let buffer = self.insertion_line().to_string();
+
+        let navigation = match self.history_navigation_policy {
+            HistoryNavigationPolicy::Automatic
+                if self.line_buffer.is_empty() || self.line_buffer.offset() != buffer.len() =>
+            {
+                HistoryNavigationQuery::Normal(self.line_buffer.clone())
+            }
+            HistoryNavigationPolicy::Automatic | HistoryNavigationPolicy::Prefix => {
+                HistoryNavigationQuery::PrefixSearch(buffer)
+            }
+            HistoryNavigationPolicy::Normal => {
+                HistoryNavigationQuery::Normal(self.line_buffer.clone())
+            }
+            HistoryNavigationPolicy::PrefixWord => HistoryNavigationQuery::PrefixSearch(
+                buffer.split_whitespace().next().unwrap_or("").to_string(),
+            ),
+            HistoryNavigationPolicy::Substring => HistoryNavigationQuery::SubstringSearch(buffer),
+        };
+        self.history.set_navigation(navigation);
     }
 
     fn search_history(&mut self) {
+        if let Some(search_panel) = &mut self.search_panel {
+            search_panel.open(self.history.as_ref());
+            self.input_mode = InputMode::HistorySearchPanel;
+            return;
+        }
+
         self.input_mode = InputMode::HistorySearch;
         self.history
             .set_navigation(HistoryNavigationQuery::SubstringSearch("".to_string()));
     }
 
+    /// Handles a key while the history search panel is open, returns whether the selected entry
+    /// is to be submitted
+    fn handle_search_panel_key(&mut self, modifiers: KeyModifiers, code: KeyCode) -> bool {
+        let search_panel = match &mut self.search_panel {
+            Some(search_panel) => search_panel,
+            None => return false,
+        };
+
+        let event = search_panel.handle_key(modifiers, code, self.history.as_ref());
+        let selected = search_panel.selected_entry().map(str::to_string);
+        match event {
+            SearchPanelEvent::Changed => return false,
+            SearchPanelEvent::Accept | SearchPanelEvent::Edit => {
+                if let Some(entry) = selected {
+                    self.set_buffer(entry);
+                }
+            }
+            SearchPanelEvent::Cancel => {}
+            SearchPanelEvent::Ignored => {
+                // Other keys close the panel and continue editing
+                if let Some(binding) = self.find_keybinding(modifiers, code) {
+                    self.input_mode = InputMode::Regular;
+                    self.run_edit_commands(&binding);
+                }
+            }
+        }
+
+        if self.input_mode == InputMode::HistorySearchPanel {
+            self.input_mode = InputMode::Regular;
+        }
+        self.need_full_repaint = true;
+
+        event == SearchPanelEvent::Accept
+    }
+
     fn cut_from_start(&mut self) {
         let insertion_offset = self.line_buffer.offset();
         if insertion_offset > 0 {
@@ -658,7 +897,9 @@ impl Reedline {
                 EditCommand::NextHistory => self.next_history(),
                 EditCommand::Up => self.up_command(),
                 EditCommand::Down => self.down_command(),
-                EditCommand::SearchHistory => self.search_history(),
+                EditCommand::SearchHistory | EditCommand::SearchHistoryForward => {
+                    self.search_history()
+                }
                 EditCommand::CutFromStart => self.cut_from_start(),
                 EditCommand::CutToEnd => self.cut_from_end(),
                 EditCommand::CutWordLeft => self.cut_word_left(),
@@ -677,6 +918,8 @@ impl Reedline {
                 EditCommand::Redo => {
                     self.line_buffer.redo();
                 }
+                EditCommand::AcceptHint => self.accept_hint(true),
+                EditCommand::AcceptHintWord => self.accept_hint(false),
                 _ => {}
             }
 
@@ -695,6 +938,8 @@ impl Reedline {
                 EditCommand::CutToEnd,
                 EditCommand::CutWordLeft,
                 EditCommand::CutWordRight,
+                EditCommand::AcceptHint,
+                EditCommand::AcceptHintWord,
             ]
             .contains(command)
             {
@@ -715,8 +960,19 @@ impl Reedline {
     }
 
     /// Reset the [`LineBuffer`] to be a line specified by `buffer`
+    ///
+    /// Newlines are kept as part of the current line so multiline entries stay editable.
     fn set_buffer(&mut self, buffer: String) {
-        self.line_buffer.set_buffer(buffer)
+        self.line_buffer.clear();
+        self.line_buffer.insert_str(&buffer);
+    }
+
+    /// Check with the [`Validator`] if the current buffer is ready to be submitted
+    fn validate_buffer(&self) -> ValidationResult {
+        match &self.validator {
+            Some(validator) => validator.validate(self.insertion_line()),
+            None => ValidationResult::Complete,
+        }
     }
 
     fn clear_to_end(&mut self) {
@@ -769,15 +1025,17 @@ impl Reedline {
     /// Repaint logic for the normal input prompt buffer
     ///
     /// Requires coordinates where the input buffer begins after the prompt.
-    fn buffer_paint(&mut self, prompt_offset: (u16, u16)) -> Result<()> {
+    fn buffer_paint(&mut self, prompt: &dyn Prompt, prompt_offset: (u16, u16)) -> Result<()> {
         let cursor_position_in_buffer = self.line_buffer.offset();
         let buffer_to_paint = self.insertion_line().to_string();
 
         self.painter.queue_buffer(
+            prompt,
             buffer_to_paint,
             prompt_offset,
             cursor_position_in_buffer,
             self.history.as_ref(),
+            self.tab_handler.menu().map(|menu| &*menu),
         )?;
         self.painter.flush()?;
 
@@ -803,11 +1061,44 @@ impl Reedline {
             buffer_to_paint,
             terminal_size,
             self.history.as_ref(),
+            self.tab_handler.menu().map(|menu| &*menu),
         )
 
         // Ok(prompt_offset)
     }
 
+    /// Lay out the completion menu or the history search panel and scroll the screen up if it needs more rows than there
+    /// are below the buffer. Returns the number of rows scrolled.
+    fn make_room_for_menu(
+        &mut self,
+        prompt_offset: (u16, u16),
+        terminal_size: (u16, u16),
+    ) -> Result<u16> {
+        // The search panel is painted below the prompt in place of the buffer
+        let searching = self.input_mode == InputMode::HistorySearchPanel;
+        let buffer_rows = if searching {
+            0
+        } else {
+            self.insertion_line().matches('\n').count() as u16
+        };
+        let available_rows = terminal_size.1.saturating_sub(buffer_rows + 1);
+        let menu_rows = match (searching, &mut self.search_panel, self.tab_handler.menu()) {
+            (true, Some(search_panel), _) => search_panel.layout(terminal_size.0, available_rows),
+            (false, _, Some(menu)) => menu.layout(terminal_size.0, available_rows),
+            _ => return Ok(0),
+        };
+
+        let rows_below = terminal_size
+            .1
+            .saturating_sub(prompt_offset.1 + buffer_rows + 1);
+        let scroll = menu_rows.saturating_sub(rows_below);
+        if scroll > 0 {
+            self.painter.scroll_up(terminal_size.1, scroll)?;
+        }
+
+        Ok(scroll)
+    }
+
     /// Repaint logic for the history reverse search
     ///
     /// Overwrites the prompt indicator and highlights the search string
@@ -843,36 +1134,76 @@ impl Reedline {
         Ok(())
     }
 
+    /// Repaint logic for the history search panel
+    ///
+    /// Overwrites the prompt indicator with the search term and lists the matches below it.
+    fn search_panel_paint(&mut self, prompt: &dyn Prompt, prompt_offset: (u16, u16)) -> Result<()> {
+        if let Some(search_panel) = &self.search_panel {
+            let status = if search_panel.is_failing() {
+                PromptHistorySearchStatus::Failing
+            } else {
+                PromptHistorySearchStatus::Passing
+            };
+            let prompt_history_search =
+                PromptHistorySearch::new(status, search_panel.term().to_string());
+            let lines = search_panel.lines();
+
+            self.painter.queue_history_search_panel(
+                prompt,
+                prompt_history_search,
+                prompt_offset.1,
+                &lines,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Append the buffer to the history and return it, the cursor is left on the next line
+    fn submit_buffer(&mut self, prompt: &dyn Prompt, prompt_offset: (u16, u16)) -> Result<Signal> {
+        let buffer = self.insertion_line().to_string();
+
+        if buffer.contains('\n') {
+            // Leave the cursor below the last line of the input
+            self.line_buffer.move_to_end();
+            self.buffer_paint(prompt, prompt_offset)?;
+        }
+
+        self.run_edit_commands(&[EditCommand::AppendToHistory, EditCommand::Clear]);
+        self.print_crlf()?;
+        self.tab_handler.reset_index();
+        self.line_buffer.reset_olds();
+
+        Ok(Signal::Success(buffer))
+    }
+
     fn update_buffer_from_history(&mut self) {
         match self.history.get_navigation() {
             HistoryNavigationQuery::Normal(original) => {
                 if let Some(buffer_to_paint) = self.history.string_at_cursor() {
-                    self.line_buffer.set_buffer(buffer_to_paint.clone());
-                    self.set_offset(buffer_to_paint.len())
+                    self.set_buffer(buffer_to_paint);
                 } else {
                     self.line_buffer = original
                 }
             }
-            HistoryNavigationQuery::PrefixSearch(prefix) => {
-                if let Some(prefix_result) = self.history.string_at_cursor() {
-                    self.line_buffer.set_buffer(prefix_result.clone());
-                    self.set_offset(prefix_result.len());
+            HistoryNavigationQuery::PrefixSearch(_)
+            | HistoryNavigationQuery::SubstringSearch(_) => {
+                if let Some(search_result) = self.history.string_at_cursor() {
+                    self.set_buffer(search_result);
                 } else {
-                    self.line_buffer.set_buffer(prefix.clone());
-                    self.set_offset(prefix.len());
+                    self.line_buffer = self.history_origin.clone();
                 }
             }
-            HistoryNavigationQuery::SubstringSearch(_) => todo!(),
         }
     }
 
     /// Helper implemting the logic for [`Reedline::read_line()`] to be wrapped
     /// in a `raw_mode` context.
     fn read_line_helper(&mut self, prompt: &dyn Prompt) -> Result<Signal> {
-        let mut terminal_size = terminal::size()?;
+        let mut terminal_size = self.terminal().size()?;
 
         let mut prompt_origin = {
-            let (column, row) = position()?;
+            let (column, row) = self.terminal().position()?;
             if (column, row) == (0, 0) {
                 (0, 0)
             } else if row + 1 == terminal_size.1 {
@@ -892,11 +1223,37 @@ impl Reedline {
         // Redraw if Ctrl-L was used
         if self.input_mode == InputMode::HistorySearch {
             self.history_search_paint(prompt)?;
+        } else if self.input_mode == InputMode::HistorySearchPanel {
+            self.search_panel_paint(prompt, prompt_offset)?;
         }
 
         loop {
-            if poll(Duration::from_secs(1))? {
-                match read()? {
+            // Check back soon for a hint that is still being computed, else once a second to
+            // repaint the clock
+            let hint_pending = self.painter.is_hint_pending();
+            let timeout = if hint_pending {
+                PENDING_HINT_POLL_INTERVAL
+            } else {
+                Duration::from_secs(1)
+            };
+
+            if self.terminal().poll(timeout)? {
+                match self.terminal().read()? {
+                    // Keys used by the history search panel while it is open
+                    Event::Key(KeyEvent { code, modifiers })
+                        if self.input_mode == InputMode::HistorySearchPanel =>
+                    {
+                        if self.handle_search_panel_key(modifiers, code) {
+                            prompt_offset =
+                                self.full_repaint(prompt, prompt_origin, terminal_size)?;
+                            return self.submit_buffer(prompt, prompt_offset);
+                        }
+                    }
+                    // Keys used by the completion menu while it is shown
+                    Event::Key(KeyEvent { code, modifiers })
+                        if self
+                            .tab_handler
+                            .handle_key(&mut self.line_buffer, modifiers, code) => {}
                     Event::Key(KeyEvent { code, modifiers }) => {
                         match (modifiers, code, self.edit_mode) {
                             (KeyModifiers::NONE, KeyCode::Tab, _) => {
@@ -944,12 +1301,13 @@ impl Reedline {
                                     0
                                 };
                                 if self.maybe_wrap(terminal_size.0, line_start, c) {
-                                    let (original_column, original_row) = position()?;
+                                    let (original_column, original_row) =
+                                        self.terminal().position()?;
                                     self.run_edit_commands(&[EditCommand::InsertChar(c)]);
 
-                                    self.buffer_paint(prompt_offset)?;
+                                    self.buffer_paint(prompt, prompt_offset)?;
 
-                                    let (new_column, _) = position()?;
+                                    let (new_column, _) = self.terminal().position()?;
 
                                     if new_column < original_column
                                         && original_row + 1 == (terminal_size.1)
@@ -966,20 +1324,18 @@ impl Reedline {
                             }
                             (KeyModifiers::NONE, KeyCode::Enter, x) if x != EditMode::ViNormal => {
                                 match self.input_mode {
-                                    InputMode::Regular | InputMode::HistoryTraversal => {
-                                        let buffer = self.insertion_line().to_string();
-
-                                        self.run_edit_commands(&[
-                                            EditCommand::AppendToHistory,
-                                            EditCommand::Clear,
-                                        ]);
-                                        self.print_crlf()?;
+                                    InputMode::Regular | InputMode::HistoryTraversal
+                                        if self.validate_buffer()
+                                            == ValidationResult::Incomplete =>
+                                    {
                                         self.tab_handler.reset_index();
-                                        self.line_buffer.reset_olds();
-
-                                        return Ok(Signal::Success(buffer));
+                                        self.run_edit_commands(&[EditCommand::InsertChar('\n')]);
+                                        self.line_buffer.set_previous_lines(false);
                                     }
-                                    InputMode::HistorySearch => {
+                                    InputMode::Regular | InputMode::HistoryTraversal => {
+                                        return self.submit_buffer(prompt, prompt_offset);
+                                    }
+                                    InputMode::HistorySearch | InputMode::HistorySearchPanel => {
                                         self.queue_prompt_indicator(prompt)?;
 
                                         if let Some(string) = self.history.string_at_cursor() {
@@ -1002,7 +1358,7 @@ impl Reedline {
                     Event::Resize(width, height) => {
                         terminal_size = (width, height);
                         // TODO properly adjusting prompt_origin on resizing while lines > 1
-                        prompt_origin.1 = position()?.1.saturating_sub(1);
+                        prompt_origin.1 = self.terminal().position()?.1.saturating_sub(1);
                         prompt_offset = self.full_repaint(prompt, prompt_origin, terminal_size)?;
                         continue;
                     }
@@ -1010,21 +1366,596 @@ impl Reedline {
                 if self.insertion_line().to_string().is_empty() {
                     self.tab_handler.reset_index();
                 }
-            } else {
+            } else if !hint_pending {
                 // No key event:
                 // Repaint the prompt for the clock
                 self.need_full_repaint = true;
             }
 
+            // Make room for the completion menu when it doesn't fit below the buffer
+            if self.input_mode != InputMode::HistorySearch {
+                let scroll = self.make_room_for_menu(prompt_offset, terminal_size)?;
+                prompt_origin.1 = prompt_origin.1.saturating_sub(scroll);
+                prompt_offset.1 = prompt_offset.1.saturating_sub(scroll);
+            }
+
             // Repainting
             if self.input_mode == InputMode::HistorySearch {
                 self.history_search_paint(prompt)?;
+            } else if self.input_mode == InputMode::HistorySearchPanel {
+                self.search_panel_paint(prompt, prompt_offset)?;
             } else if self.need_full_repaint {
                 prompt_offset = self.full_repaint(prompt, prompt_origin, terminal_size)?;
                 self.need_full_repaint = false;
             } else {
-                self.buffer_paint(prompt_offset)?;
+                self.buffer_paint(prompt, prompt_offset)?;
+            }
+
+            // A multiline buffer reaching beyond the last row scrolled the screen up
+            let buffer_rows = self.insertion_line().matches('\n').count() as u16;
+            let overflow =
+                (prompt_offset.1 + buffer_rows).saturating_sub(terminal_size.1.saturating_sub(1));
+            if overflow > 0
+                && self.input_mode != InputMode::HistorySearch
+                && self.input_mode != InputMode::HistorySearchPanel
+            {
+                prompt_origin.1 = prompt_origin.1.saturating_sub(overflow);
+                prompt_offset.1 = prompt_offset.1.saturating_sub(overflow);
             }
         }
     }
 }
+
+/// The text up to the end of the first word, including the separators in front of it
+fn first_word(text: &str) -> &str {
+    let start = text
+        .find(|c: char| c.is_alphanumeric())
+        .unwrap_or(text.len());
+    let end = text[start..]
+        .find(|c: char| !c.is_alphanumeric())
+        .map_or(text.len(), |end| start + end);
+
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{DefaultPrompt, VirtualTerminal},
+        pretty_assertions::assert_eq,
+    };
+
+    fn key(modifiers: KeyModifiers, code: KeyCode) -> Event {
+        Event::Key(KeyEvent::new(code, modifiers))
+    }
+
+    fn editor_with_terminal() -> (Reedline, VirtualTerminal) {
+        let terminal = VirtualTerminal::new(40, 10);
+        let line_editor = Reedline::new().with_terminal(Box::new(terminal.clone()));
+        (line_editor, terminal)
+    }
+
+    #[test]
+    fn typed_line_is_returned_and_painted() {
+        let (mut line_editor, terminal) = editor_with_terminal();
+        terminal.type_str("hello world");
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "hello world"));
+        assert_eq!(terminal.screen()[1], "〉hello world");
+        assert_eq!(terminal.cursor(), (0, 2));
+        assert!(!terminal.is_raw_mode());
+    }
+
+    #[test]
+    fn edit_commands_are_applied_before_painting() {
+        let (mut line_editor, terminal) = editor_with_terminal();
+        terminal.type_str("wrld");
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('a')));
+        terminal.push_key(KeyCode::Right);
+        terminal.type_str("o");
+        terminal.push_key(KeyCode::End);
+        terminal.push_key(KeyCode::Backspace);
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "worl"));
+        assert_eq!(terminal.screen()[1], "〉worl");
+    }
+
+    #[test]
+    fn ctrl_d_on_empty_buffer_signals_end() {
+        let (mut line_editor, terminal) = editor_with_terminal();
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('d')));
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::CtrlD));
+    }
+
+    #[test]
+    fn keybindings_can_be_configured_for_vi_insert_mode() {
+        let mut keybindings = default_vi_insert_keybindings();
+        keybindings.add_binding(
+            KeyModifiers::ALT,
+            KeyCode::Char('a'),
+            vec![EditCommand::MoveToStart],
+        );
+        let (line_editor, terminal) = editor_with_terminal();
+        let mut line_editor = line_editor
+            .with_edit_mode(EditMode::ViInsert)
+            .with_edit_mode_keybindings(EditMode::ViInsert, keybindings);
+        terminal.type_str("world");
+        terminal.push_event(key(KeyModifiers::ALT, KeyCode::Char('a')));
+        terminal.type_str("hello ");
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "hello world"));
+    }
+
+    #[test]
+    fn previous_entry_is_recalled_from_history() {
+        let (mut line_editor, terminal) = editor_with_terminal();
+        terminal.type_str("first");
+        terminal.push_key(KeyCode::Enter);
+        terminal.push_key(KeyCode::Up);
+        terminal.push_key(KeyCode::Enter);
+
+        line_editor.read_line(&DefaultPrompt::default()).unwrap();
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "first"));
+        assert_eq!(terminal.screen()[4], "〉first");
+    }
+
+    #[test]
+    fn incomplete_input_continues_on_a_new_line() {
+        let (line_editor, terminal) = editor_with_terminal();
+        let mut line_editor = line_editor.with_validator(Box::new(crate::DefaultValidator));
+        terminal.type_str("each {");
+        terminal.push_key(KeyCode::Enter);
+        terminal.type_str("echo $it }");
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "each {\necho $it }"));
+        assert_eq!(terminal.screen()[1..3], ["〉each {", "::: echo $it }"]);
+        assert_eq!(terminal.cursor(), (0, 3));
+    }
+
+    #[test]
+    fn multiline_input_scrolls_at_the_bottom_of_the_screen() {
+        let terminal = VirtualTerminal::new(40, 3);
+        let mut line_editor = Reedline::new()
+            .with_terminal(Box::new(terminal.clone()))
+            .with_validator(Box::new(crate::DefaultValidator));
+        terminal.type_str("(");
+        terminal.push_key(KeyCode::Enter);
+        terminal.type_str("1");
+        terminal.push_key(KeyCode::Enter);
+        terminal.type_str(")");
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "(\n1\n)"));
+        assert_eq!(terminal.screen()[..2], ["::: 1", "::: )"]);
+    }
+
+    #[test]
+    fn multiline_entry_is_recalled_from_history_as_a_whole() {
+        let (line_editor, terminal) = editor_with_terminal();
+        let mut line_editor = line_editor.with_validator(Box::new(crate::DefaultValidator));
+        terminal.type_str("[");
+        terminal.push_key(KeyCode::Enter);
+        terminal.type_str("]");
+        terminal.push_key(KeyCode::Enter);
+        terminal.push_key(KeyCode::Up);
+        terminal.push_key(KeyCode::Enter);
+
+        line_editor.read_line(&DefaultPrompt::default()).unwrap();
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "[\n]"));
+    }
+
+    #[test]
+    fn tab_completes_on_a_continuation_line() {
+        let (line_editor, terminal) = editor_with_terminal();
+        let completer = crate::DefaultCompleter::new(vec!["hello".into()]);
+        let mut line_editor = line_editor
+            .with_validator(Box::new(crate::DefaultValidator))
+            .with_completion_action_handler(Box::new(
+                crate::DefaultCompletionActionHandler::default()
+                    .with_completer(Box::new(completer)),
+            ));
+        terminal.type_str("(");
+        terminal.push_key(KeyCode::Enter);
+        terminal.type_str("echo hel");
+        terminal.push_key(KeyCode::Tab);
+        terminal.type_str(")");
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "(\necho hello)"));
+    }
+
+    fn editor_with_completion_menu(terminal: &VirtualTerminal) -> Reedline {
+        let completer = crate::DefaultCompleter::new(vec!["hello".into(), "help".into()]);
+        Reedline::new()
+            .with_terminal(Box::new(terminal.clone()))
+            .with_completion_action_handler(Box::new(
+                crate::MenuCompletionActionHandler::default().with_completer(Box::new(completer)),
+            ))
+    }
+
+    #[test]
+    fn completion_menu_is_painted_below_the_buffer() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_completion_menu(&terminal);
+        terminal.type_str("he");
+        terminal.push_key(KeyCode::Tab);
+        terminal.push_key(KeyCode::Tab);
+
+        assert!(line_editor.read_line(&DefaultPrompt::default()).is_err());
+
+        assert_eq!(terminal.screen()[1..3], ["〉help", "hello  help"]);
+        assert_eq!(terminal.cursor(), (6, 1));
+    }
+
+    #[test]
+    fn accepted_completion_closes_the_menu() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_completion_menu(&terminal);
+        terminal.type_str("he");
+        terminal.push_key(KeyCode::Tab);
+        terminal.push_key(KeyCode::Tab);
+        terminal.push_key(KeyCode::Enter);
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "help"));
+        assert_eq!(terminal.screen()[1..3], ["〉help", ""]);
+    }
+
+    #[test]
+    fn completion_menu_scrolls_at_the_bottom_of_the_screen() {
+        let terminal = VirtualTerminal::new(40, 3);
+        let mut line_editor = editor_with_completion_menu(&terminal);
+        terminal.type_str("he");
+        terminal.push_key(KeyCode::Tab);
+
+        assert!(line_editor.read_line(&DefaultPrompt::default()).is_err());
+
+        assert_eq!(terminal.screen()[1..], ["〉hello", "hello  help"]);
+        assert_eq!(terminal.cursor(), (7, 1));
+    }
+
+    fn editor_with_hints(terminal: &VirtualTerminal) -> Reedline {
+        let completer = crate::DefaultCompleter::new(vec!["hello world".into()]);
+        Reedline::new()
+            .with_terminal(Box::new(terminal.clone()))
+            .with_hinter(Box::new(
+                DefaultHinter::default().with_completer(Box::new(completer)),
+            ))
+    }
+
+    #[test]
+    fn hint_is_accepted_at_the_end_of_the_buffer() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_hints(&terminal);
+        terminal.type_str("he");
+        terminal.push_key(KeyCode::Right);
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "hello world"));
+    }
+
+    #[test]
+    fn hint_is_accepted_word_by_word() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_hints(&terminal);
+        terminal.type_str("he");
+        terminal.push_event(key(KeyModifiers::ALT, KeyCode::Char('f')));
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "hello"));
+    }
+
+    #[test]
+    fn only_suggestions_extending_the_buffer_are_hinted() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let completer = crate::DefaultCompleter::new(vec!["git commit".into(), "gcc".into()])
+            .with_fuzzy_match();
+        let mut line_editor = Reedline::new()
+            .with_terminal(Box::new(terminal.clone()))
+            .with_hinter(Box::new(
+                DefaultHinter::default().with_completer(Box::new(completer)),
+            ));
+        terminal.type_str("gco");
+        terminal.push_key(KeyCode::Right);
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "gco"));
+    }
+
+    // Completes file names the way a shell quotes them
+    struct QuotingCompleter;
+
+    impl crate::Completer for QuotingCompleter {
+        fn complete(&self, _line: &str, pos: usize) -> Vec<(crate::Span, String)> {
+            vec![(crate::Span::new(0, pos), "\"my docs/\"".into())]
+        }
+    }
+
+    #[test]
+    fn quoted_suggestions_are_not_hinted() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = Reedline::new()
+            .with_terminal(Box::new(terminal.clone()))
+            .with_hinter(Box::new(
+                DefaultHinter::default().with_completer(Box::new(QuotingCompleter)),
+            ));
+        terminal.type_str("my");
+        terminal.push_key(KeyCode::Right);
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "my"));
+    }
+
+    #[test]
+    fn right_moves_the_cursor_before_the_end_of_the_buffer() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_hints(&terminal);
+        terminal.type_str("he");
+        terminal.push_key(KeyCode::Left);
+        terminal.push_key(KeyCode::Right);
+        terminal.type_str("y");
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "hey"));
+    }
+
+    fn editor_with_history(terminal: &VirtualTerminal) -> Reedline {
+        let mut line_editor = Reedline::new().with_terminal(Box::new(terminal.clone()));
+        for entry in &["cargo build", "git status", "cargo test", "cargo run"] {
+            line_editor.history.append(entry.to_string());
+        }
+        line_editor
+    }
+
+    #[test]
+    fn repeated_reverse_search_steps_to_older_matches() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_history(&terminal);
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('r')));
+        terminal.type_str("cargo");
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('r')));
+
+        assert!(line_editor.read_line(&DefaultPrompt::default()).is_err());
+        assert_eq!(terminal.screen()[1], "(reverse-search: cargo)cargo test");
+
+        // Stays on the oldest match
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('r')));
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('r')));
+        terminal.push_key(KeyCode::Enter);
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "cargo build"));
+    }
+
+    #[test]
+    fn forward_search_steps_to_newer_matches() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_history(&terminal);
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('r')));
+        terminal.type_str("cargo");
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('r')));
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('r')));
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('s')));
+        terminal.push_key(KeyCode::Enter);
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "cargo test"));
+    }
+
+    #[test]
+    fn forward_search_stays_on_the_newest_match() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_history(&terminal);
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('s')));
+        terminal.type_str("git");
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('s')));
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('s')));
+        terminal.push_key(KeyCode::Enter);
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "git status"));
+    }
+
+    #[test]
+    fn history_traversal_follows_a_substring_query() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_history(&terminal)
+            .with_history_navigation_policy(HistoryNavigationPolicy::Substring);
+        line_editor.set_buffer("test".to_string());
+
+        line_editor.previous_history();
+        assert_eq!(line_editor.insertion_line(), "cargo test");
+        line_editor.previous_history();
+        assert_eq!(line_editor.insertion_line(), "cargo test");
+        line_editor.next_history();
+        assert_eq!(line_editor.insertion_line(), "test");
+    }
+
+    #[test]
+    fn normal_policy_browses_all_entries() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_history(&terminal)
+            .with_history_navigation_policy(HistoryNavigationPolicy::Normal);
+        terminal.type_str("git");
+        terminal.push_key(KeyCode::Up);
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "cargo run"));
+    }
+
+    #[test]
+    fn prefix_policy_ignores_the_cursor_position() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_history(&terminal)
+            .with_history_navigation_policy(HistoryNavigationPolicy::Prefix);
+        terminal.type_str("git");
+        terminal.push_key(KeyCode::Left);
+        terminal.push_key(KeyCode::Up);
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "git status"));
+    }
+
+    #[test]
+    fn prefix_word_policy_restores_the_buffer_past_the_newest_match() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_history(&terminal)
+            .with_history_navigation_policy(HistoryNavigationPolicy::PrefixWord);
+        terminal.type_str("cargo t");
+        terminal.push_key(KeyCode::Up);
+        terminal.push_key(KeyCode::Up);
+
+        assert!(line_editor.read_line(&DefaultPrompt::default()).is_err());
+        assert_eq!(terminal.screen()[1], "〉cargo test");
+
+        terminal.push_key(KeyCode::Down);
+        terminal.push_key(KeyCode::Down);
+        terminal.type_str("ree");
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "cargo tree"));
+    }
+
+    #[test]
+    fn up_can_skip_the_lines_of_a_multiline_buffer() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_history(&terminal)
+            .with_history_navigation_policy(HistoryNavigationPolicy::Normal)
+            .with_up_down_in_buffer_first(false);
+        terminal.type_str("echo a");
+        terminal.push_event(key(KeyModifiers::ALT, KeyCode::Enter));
+        terminal.type_str("echo b");
+        terminal.push_key(KeyCode::Up);
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "cargo run"));
+    }
+
+    fn editor_with_search_panel(terminal: &VirtualTerminal) -> Reedline {
+        let mut line_editor = Reedline::new()
+            .with_terminal(Box::new(terminal.clone()))
+            .with_history_search_panel(crate::HistorySearchPanel::default());
+        for entry in &["git checkout main", "cargo test", "git commit"] {
+            line_editor.history.append(entry.to_string());
+        }
+        line_editor
+    }
+
+    #[test]
+    fn search_panel_lists_the_matches_below_the_prompt() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_search_panel(&terminal);
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('r')));
+        terminal.type_str("gco");
+
+        assert!(line_editor.read_line(&DefaultPrompt::default()).is_err());
+
+        assert_eq!(
+            terminal.screen()[1..5],
+            [
+                "(reverse-search: gco)",
+                "git commit",
+                "git checkout main",
+                "-- fuzzy search, 2 of 2 --"
+            ]
+        );
+    }
+
+    #[test]
+    fn search_panel_submits_the_selected_entry() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_search_panel(&terminal);
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('r')));
+        terminal.type_str("gco");
+        terminal.push_key(KeyCode::Down);
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "git checkout main"));
+        assert_eq!(terminal.screen()[1..3], ["〉git checkout main", ""]);
+    }
+
+    #[test]
+    fn search_panel_puts_the_selected_entry_up_for_editing() {
+        let terminal = VirtualTerminal::new(40, 10);
+        let mut line_editor = editor_with_search_panel(&terminal);
+        terminal.push_event(key(KeyModifiers::CONTROL, KeyCode::Char('r')));
+        terminal.type_str("test");
+        terminal.push_key(KeyCode::Tab);
+        terminal.type_str(" --all");
+        terminal.push_key(KeyCode::Enter);
+
+        let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+
+        assert!(matches!(sig, Signal::Success(buffer) if buffer == "cargo test --all"));
+    }
+
+    #[test]
+    fn first_word_keeps_leading_separators() {
+        assert_eq!(first_word("llo world"), "llo");
+        assert_eq!(first_word(" world reedline"), " world");
+        assert_eq!(first_word("/src/main.rs"), "/src");
+        assert_eq!(first_word("--"), "--");
+    }
+
+    #[test]
+    fn running_out_of_events_returns_an_error() {
+        let (mut line_editor, terminal) = editor_with_terminal();
+        terminal.type_str("unfinished");
+
+        assert!(line_editor.read_line(&DefaultPrompt::default()).is_err());
+        assert_eq!(terminal.pending_events(), 0);
+        assert!(!terminal.is_raw_mode());
+    }
+}
@@ -65,9 +65,12 @@ pub enum EditCommand {
     /// Navigate to the next historic buffer
     NextHistory,
 
-    /// Search the history for a string
+    /// Search the history for a string, stepping to older matches when repeated
     SearchHistory,
 
+    /// Search the history for a string, stepping to newer matches when repeated
+    SearchHistoryForward,
+
     /// Clear the current buffer
     Clear,
 
@@ -115,6 +118,13 @@ pub enum EditCommand {
 
     /// Redo an edit command from the undo history
     Redo,
+
+    /// Insert the hint shown after the cursor, when the cursor is at the end of the buffer
+    AcceptHint,
+
+    /// Insert the next word of the hint shown after the cursor, when the cursor is at the end
+    /// of the buffer
+    AcceptHintWord,
 }
 
 /// The edit mode [`crate::Reedline`] is currently in. Influences keybindings and prompt.
@@ -129,3 +139,24 @@ pub enum EditMode {
     /// Vi insertion mode
     ViInsert,
 }
+
+/// Which history entries Up and Down browse through, decided from the buffer when the
+/// browsing starts
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HistoryNavigationPolicy {
+    /// All entries if the buffer is empty or the cursor is not at its end, otherwise the
+    /// entries starting with the buffer. Used by default
+    Automatic,
+
+    /// All entries, regardless of the buffer
+    Normal,
+
+    /// The entries starting with the buffer
+    Prefix,
+
+    /// The entries starting with the first word of the buffer
+    PrefixWord,
+
+    /// The entries containing the buffer
+    Substring,
+}
@@ -0,0 +1,338 @@
+use {
+    crate::{Completer, Span, Suggestion, SuggestionCompleter, SuggestionKind},
+    std::{
+        env, fs,
+        path::{is_separator, Path, PathBuf},
+    },
+};
+
+/// A completer for paths on the filesystem
+///
+/// The word under the cursor is completed with the entries of the directory it points to,
+/// relative to the current directory unless it is absolute or starts with `~`. Directories
+/// get a trailing `/` so the next Tab descends into them, and entries starting with a `.` are
+/// only listed when the word does too. Results containing spaces are wrapped in quotes, or
+/// have their spaces escaped with a backslash when configured with
+/// [`FilenameCompleter::with_escaping`].
+///
+/// # Example
+/// ```rust,no_run
+/// use reedline::{DefaultCompletionActionHandler, FilenameCompleter, Reedline};
+///
+/// let completer = Box::new(FilenameCompleter::new());
+///
+/// let mut line_editor = Reedline::new().with_completion_action_handler(Box::new(
+///     DefaultCompletionActionHandler::default().with_completer(completer),
+/// ));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FilenameCompleter {
+    cwd: Option<PathBuf>,
+    dirs_only: bool,
+    escaping: bool,
+}
+
+impl FilenameCompleter {
+    /// Create a completer for paths relative to the current directory of the process
+    pub fn new() -> FilenameCompleter {
+        FilenameCompleter::default()
+    }
+
+    /// A builder that completes paths relative to `cwd` instead of the current directory
+    pub fn with_cwd(mut self, cwd: impl Into<PathBuf>) -> FilenameCompleter {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    /// A builder that only completes directories, for commands like `cd`
+    pub fn with_dirs_only(mut self) -> FilenameCompleter {
+        self.dirs_only = true;
+        self
+    }
+
+    /// A builder that escapes spaces with a backslash instead of quoting the result
+    pub fn with_escaping(mut self) -> FilenameCompleter {
+        self.escaping = true;
+        self
+    }
+
+    fn base_dir(&self) -> Option<PathBuf> {
+        match &self.cwd {
+            Some(cwd) => Some(cwd.clone()),
+            None => env::current_dir().ok(),
+        }
+    }
+
+    // Quote or escape the path so it stays a single word
+    fn protect(&self, path: &str, quote: Option<char>, is_dir: bool) -> String {
+        match quote {
+            // A quote typed by the user is kept, and left open for directories so the next Tab
+            // completes inside of them
+            Some(quote) if is_dir => format!("{}{}", quote, path),
+            Some(quote) => format!("{}{}{}", quote, path, quote),
+            None if !path.contains(' ') => path.to_string(),
+            None if self.escaping => path.replace(' ', "\\ "),
+            None => format!("\"{}\"", path),
+        }
+    }
+}
+
+impl SuggestionCompleter for FilenameCompleter {
+    fn suggest(&self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let start = word_start(line, pos);
+        let (word, quote) = unquote(&line[start..pos]);
+
+        let (dir, prefix) = split_word(&word);
+
+        let listed_dir = match expand_home(dir, home_dir()) {
+            Some(expanded) if expanded.is_absolute() => expanded,
+            Some(expanded) => match self.base_dir() {
+                Some(base_dir) => base_dir.join(expanded),
+                None => return vec![],
+            },
+            None => return vec![],
+        };
+
+        let entries = match fs::read_dir(listed_dir) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+
+        let mut entries: Vec<(String, bool)> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.'))
+                {
+                    return None;
+                }
+
+                // Follow symlinks, so links to directories can be descended into
+                let is_dir = fs::metadata(entry.path())
+                    .map(|metadata| metadata.is_dir())
+                    .unwrap_or(false);
+                if self.dirs_only && !is_dir {
+                    return None;
+                }
+
+                Some((name, is_dir))
+            })
+            .collect();
+        entries.sort();
+
+        let span = Span::new(start, pos);
+        entries
+            .into_iter()
+            .map(|(name, is_dir)| {
+                let (display, kind) = if is_dir {
+                    (format!("{}/", name), SuggestionKind::Directory)
+                } else {
+                    (name, SuggestionKind::File)
+                };
+
+                Suggestion::new(
+                    self.protect(&format!("{}{}", dir, display), quote, is_dir),
+                    span,
+                )
+                .with_display(display)
+                .with_kind(kind)
+                .with_append_whitespace(!is_dir)
+            })
+            .collect()
+    }
+}
+
+impl Completer for FilenameCompleter {
+    /// Returns the paths completing the word under the cursor
+    ///
+    /// # Example
+    /// ```
+    /// use reedline::{Completer, FilenameCompleter, Span};
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// std::fs::create_dir(dir.path().join("src")).unwrap();
+    ///
+    /// let completer = FilenameCompleter::new().with_cwd(dir.path());
+    /// assert_eq!(completer.complete("cd sr", 5), vec![(Span::new(3, 5), "src/".into())]);
+    /// ```
+    fn complete(&self, line: &str, pos: usize) -> Vec<(Span, String)> {
+        self.suggest(line, pos)
+            .into_iter()
+            .map(|suggestion| (suggestion.span, suggestion.value))
+            .collect()
+    }
+}
+
+/// The start of the word ending at `pos`, words are separated by whitespace outside of quotes
+/// that is not escaped with a backslash
+fn word_start(line: &str, pos: usize) -> usize {
+    let mut start = 0;
+    let mut quote = None;
+    let mut chars = line[..pos].char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') if index == start => quote = Some(c),
+            (None, '\\') if matches!(chars.peek(), Some((_, ' '))) => {
+                chars.next();
+            }
+            (None, c) if c.is_whitespace() => start = index + c.len_utf8(),
+            _ => {}
+        }
+    }
+
+    start
+}
+
+/// Remove the quotes or escapes from a word, returns the quote it started with
+fn unquote(word: &str) -> (String, Option<char>) {
+    match word.chars().next() {
+        Some(quote) if quote == '"' || quote == '\'' => {
+            let inner = &word[1..];
+            (
+                inner.strip_suffix(quote).unwrap_or(inner).to_string(),
+                Some(quote),
+            )
+        }
+        _ => (word.replace("\\ ", " "), None),
+    }
+}
+
+/// Split a word into the directory to list and the prefix of the entries, a bare `~` lists
+/// the home directory
+fn split_word(word: &str) -> (&str, &str) {
+    if word == "~" {
+        return ("~/", "");
+    }
+    match word.rfind(is_separator) {
+        Some(index) => (&word[..=index], &word[index + 1..]),
+        None => ("", word),
+    }
+}
+
+/// Expand a leading `~` to the home directory, `None` when there is no home directory
+fn expand_home(dir: &str, home: Option<PathBuf>) -> Option<PathBuf> {
+    match dir.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(is_separator) => {
+            Some(home?.join(rest.trim_start_matches(is_separator)))
+        }
+        _ => Some(Path::new(dir).to_path_buf()),
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" }).map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::{tempdir, TempDir};
+
+    fn fixture() -> TempDir {
+        let tmp = tempdir().unwrap();
+        fs::write(tmp.path().join("alpha.txt"), "").unwrap();
+        fs::write(tmp.path().join(".alpha"), "").unwrap();
+        fs::create_dir(tmp.path().join("alpine")).unwrap();
+        fs::create_dir(tmp.path().join("my docs")).unwrap();
+        fs::write(tmp.path().join("my docs").join("notes.md"), "").unwrap();
+        tmp
+    }
+
+    fn values(completer: &FilenameCompleter, line: &str) -> Vec<String> {
+        completer
+            .suggest(line, line.len())
+            .into_iter()
+            .map(|suggestion| suggestion.value)
+            .collect()
+    }
+
+    #[test]
+    fn lists_matching_entries_with_directories_marked() {
+        let tmp = fixture();
+        let completer = FilenameCompleter::new().with_cwd(tmp.path());
+
+        let suggestions = completer.suggest("cat al", 6);
+
+        assert_eq!(
+            suggestions,
+            vec![
+                Suggestion::new("alpha.txt", Span::new(4, 6))
+                    .with_display("alpha.txt")
+                    .with_kind(SuggestionKind::File)
+                    .with_append_whitespace(true),
+                Suggestion::new("alpine/", Span::new(4, 6))
+                    .with_display("alpine/")
+                    .with_kind(SuggestionKind::Directory),
+            ]
+        );
+        assert_eq!(values(&completer, "cat .al"), vec![".alpha"]);
+    }
+
+    #[test]
+    fn filters_to_directories() {
+        let tmp = fixture();
+        let completer = FilenameCompleter::new()
+            .with_cwd(tmp.path())
+            .with_dirs_only();
+
+        assert_eq!(values(&completer, "cd al"), vec!["alpine/"]);
+        assert_eq!(values(&completer, "cd "), vec!["alpine/", "\"my docs/\""]);
+    }
+
+    #[test]
+    fn quotes_or_escapes_spaces() {
+        let tmp = fixture();
+        let completer = FilenameCompleter::new().with_cwd(tmp.path());
+
+        assert_eq!(values(&completer, "cd my"), vec!["\"my docs/\""]);
+        assert_eq!(
+            values(&completer, "cat \"my docs/"),
+            vec!["\"my docs/notes.md\""]
+        );
+        assert_eq!(
+            values(&completer, "cat 'my docs/no"),
+            vec!["'my docs/notes.md'"]
+        );
+        assert_eq!(
+            values(&completer.with_escaping(), "cat my\\ docs/"),
+            vec!["my\\ docs/notes.md"]
+        );
+    }
+
+    #[test]
+    fn finds_the_word_under_the_cursor() {
+        assert_eq!(word_start("cat al", 6), 4);
+        assert_eq!(word_start("cat \"my docs/n", 14), 4);
+        assert_eq!(word_start("cat my\\ docs/n", 14), 4);
+        assert_eq!(word_start("cat ", 4), 4);
+    }
+
+    #[test]
+    fn splits_the_directory_from_the_prefix() {
+        assert_eq!(split_word("src/ma"), ("src/", "ma"));
+        assert_eq!(split_word("ma"), ("", "ma"));
+        assert_eq!(split_word("~/"), ("~/", ""));
+        assert_eq!(split_word("~"), ("~/", ""));
+        assert_eq!(split_word("~us"), ("", "~us"));
+    }
+
+    #[test]
+    fn expands_the_home_directory() {
+        let home = Some(PathBuf::from("/home/user"));
+
+        assert_eq!(
+            expand_home("~/src/", home.clone()),
+            Some(PathBuf::from("/home/user/src/"))
+        );
+        assert_eq!(
+            expand_home("~", home.clone()),
+            Some(PathBuf::from("/home/user"))
+        );
+        assert_eq!(expand_home("~user/", home), Some(PathBuf::from("~user/")));
+        assert_eq!(expand_home("~/", None), None);
+    }
+}
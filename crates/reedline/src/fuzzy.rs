@@ -0,0 +1,176 @@
+// Points for every matched character
+const MATCH_SCORE: i32 = 16;
+
+// Extra points for a character matched right after the previous one
+const CONSECUTIVE_BONUS: i32 = 16;
+
+// Extra points for a character matched at the start of a word
+const BOUNDARY_BONUS: i32 = 24;
+
+// Points lost for every character skipped between two matched ones
+const GAP_PENALTY: i32 = 2;
+
+// Points lost for every character skipped before the first matched one
+const LEADING_GAP_PENALTY: i32 = 1;
+
+/// Score how well `pattern` matches `candidate` as a subsequence, `None` when it doesn't match.
+///
+/// Matches of consecutive characters and at word boundaries score higher, gaps between the
+/// matched characters lower, so `gco` scores higher for `git checkout` than for `gecko`.
+/// The best scoring placement of the pattern in the candidate is used.
+pub(crate) fn fuzzy_score(pattern: &str, candidate: &str, ignore_case: bool) -> Option<i32> {
+    fuzzy_match(pattern, candidate, ignore_case).map(|(score, _)| score)
+}
+
+/// Like [`fuzzy_score`], also returns the indices of the matched characters of the candidate
+pub(crate) fn fuzzy_match(
+    pattern: &str,
+    candidate: &str,
+    ignore_case: bool,
+) -> Option<(i32, Vec<usize>)> {
+    let normalize = |c: char| {
+        if ignore_case {
+            c.to_lowercase().next().unwrap_or(c)
+        } else {
+            c
+        }
+    };
+    let pattern: Vec<char> = pattern.chars().map(normalize).collect();
+    let chars: Vec<char> = candidate.chars().map(normalize).collect();
+    if pattern.is_empty() {
+        return Some((0, vec![]));
+    }
+
+    // Cheap check before scoring, most candidates don't match at all
+    let mut remaining = pattern.iter().peekable();
+    for c in &chars {
+        if remaining.peek() == Some(&c) {
+            remaining.next();
+        }
+    }
+    if remaining.peek().is_some() {
+        return None;
+    }
+
+    let original: Vec<char> = candidate.chars().collect();
+    // The best score of the pattern so far, with its last character matched at each index,
+    // and where the character before it was matched
+    let mut best: Vec<Option<i32>> = vec![None; chars.len()];
+    let mut previous: Vec<Vec<usize>> = Vec::with_capacity(pattern.len());
+    for (i, p) in pattern.iter().enumerate() {
+        let mut next = vec![None; chars.len()];
+        let mut from = vec![0; chars.len()];
+        // The best match of the previous character at least two characters back, with the
+        // gap penalty up to here added back, so it only has to be looked at once
+        let mut gapped: Option<(i32, usize)> = None;
+
+        for (j, c) in chars.iter().enumerate() {
+            if j >= 2 {
+                if let Some(score) = best[j - 2] {
+                    let score = score + GAP_PENALTY * (j - 2) as i32;
+                    if !matches!(gapped, Some((best_score, _)) if best_score > score) {
+                        gapped = Some((score, j - 2));
+                    }
+                }
+            }
+            if c != p {
+                continue;
+            }
+
+            let bonus = if is_boundary(&original, j) {
+                MATCH_SCORE + BOUNDARY_BONUS
+            } else {
+                MATCH_SCORE
+            };
+
+            if i == 0 {
+                next[j] = Some(bonus - LEADING_GAP_PENALTY * j as i32);
+                continue;
+            }
+
+            let consecutive = j
+                .checked_sub(1)
+                .and_then(|k| best[k].map(|score| (score + CONSECUTIVE_BONUS, k)));
+            let gap = gapped.map(|(score, k)| (score - GAP_PENALTY * (j - 1) as i32, k));
+            let candidates = consecutive.into_iter().chain(gap);
+            if let Some((score, k)) = candidates.max_by_key(|(score, _)| *score) {
+                next[j] = Some(score + bonus);
+                from[j] = k;
+            }
+        }
+        best = next;
+        previous.push(from);
+    }
+
+    let (mut index, score) = best
+        .iter()
+        .enumerate()
+        .filter_map(|(index, score)| score.map(|score| (index, score)))
+        .max_by_key(|(_, score)| *score)?;
+
+    let mut positions = vec![index; pattern.len()];
+    for i in (1..pattern.len()).rev() {
+        index = previous[i][index];
+        positions[i - 1] = index;
+    }
+
+    Some((score, positions))
+}
+
+// Whether a word starts at the index, after a separator or at a camelCase hump
+fn is_boundary(chars: &[char], index: usize) -> bool {
+    match index.checked_sub(1).map(|previous| chars[previous]) {
+        None => true,
+        Some(previous) => {
+            !previous.is_alphanumeric() || (previous.is_lowercase() && chars[index].is_uppercase())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn matches_subsequences_only() {
+        assert!(fuzzy_score("gco", "git checkout", false).is_some());
+        assert!(fuzzy_score("gc", "gecko", false).is_some());
+        assert_eq!(fuzzy_score("ogc", "git checkout", false), None);
+        assert_eq!(fuzzy_score("", "anything", false), Some(0));
+    }
+
+    #[test]
+    fn word_boundaries_and_consecutive_matches_score_higher() {
+        assert!(fuzzy_score("gc", "git checkout", false) > fuzzy_score("gc", "gecko", false));
+        assert!(
+            fuzzy_score("che", "git checkout", false) > fuzzy_score("cho", "git checkout", false)
+        );
+        assert!(fuzzy_score("hw", "helloWorld", true) > fuzzy_score("hw", "showdown", true));
+    }
+
+    #[test]
+    fn matched_characters_are_returned() {
+        assert_eq!(
+            fuzzy_match("gco", "git checkout", false),
+            Some((
+                fuzzy_score("gco", "git checkout", false).unwrap(),
+                vec![0, 4, 9]
+            ))
+        );
+        assert_eq!(
+            fuzzy_match("lo", "hello", false).map(|(_, p)| p),
+            Some(vec![3, 4])
+        );
+        assert_eq!(fuzzy_match("xyz", "hello", false), None);
+    }
+
+    #[test]
+    fn case_is_ignored_on_request() {
+        assert_eq!(fuzzy_score("GC", "git checkout", false), None);
+        assert_eq!(
+            fuzzy_score("GC", "git checkout", true),
+            fuzzy_score("gc", "git checkout", false)
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use {
-    crate::{Completer, DefaultCompleter, History},
+    crate::{Completer, CompleterAdapter, History, Span, Suggestion, SuggestionCompleter},
     nu_ansi_term::{Color, Style},
 };
 
@@ -8,33 +8,65 @@ use {
 pub trait Hinter {
     /// Handle the hinting duty by using the line, position, and current history
     fn handle(&mut self, line: &str, pos: usize, history: &dyn History) -> String;
+
+    /// The hint last returned by `handle` without any styling, the text inserted when the hint
+    /// is accepted
+    fn current_hint(&self) -> String {
+        String::new()
+    }
+
+    /// Whether the hint for the last line is still being computed, the editor repaints the
+    /// buffer shortly to show it once it arrives
+    fn is_pending(&self) -> bool {
+        false
+    }
 }
 
 /// A default example hinter that use the completions or the history to show a hint to the user
 pub struct DefaultHinter {
-    completer: Option<Box<dyn Completer>>,
+    completer: Option<Box<dyn SuggestionCompleter>>,
     history: bool,
     style: Style,
     inside_line: bool,
+    description: bool,
+    current_hint: String,
 }
 
 impl Hinter for DefaultHinter {
     fn handle(&mut self, line: &str, pos: usize, history: &dyn History) -> String {
         let mut completions = vec![];
         let mut output = String::new();
+        self.current_hint = String::new();
 
         if pos == line.len() || self.inside_line {
             if let Some(c) = &self.completer {
-                completions = c.complete(line, pos);
-            } else if self.history {
-                let history: Vec<String> = history.iter_chronologic().cloned().collect();
-                completions = DefaultCompleter::new(history).complete(line, pos);
+                completions = c.suggest(line, pos);
+            } else if self.history && pos > 0 {
+                // The most recent command starting with the text before the cursor
+                if let Some(entry) = history.newest_with_prefix(&line[..pos]) {
+                    completions = vec![Suggestion::new(entry, Span::new(0, pos))];
+                }
             }
 
-            if !completions.is_empty() {
-                let mut hint = completions[0].1.clone();
-                let span = completions[0].0;
-                hint.replace_range(0..(span.end - span.start), "");
+            // Only a suggestion extending the typed text can be shown, and accepted, as the
+            // rest of it. Fuzzy or quoted suggestions don't start with what was typed.
+            let extending = completions.iter().find(|suggestion| {
+                matches!(
+                    line.get(suggestion.span.start..suggestion.span.end),
+                    Some(typed) if suggestion.value.starts_with(typed)
+                )
+            });
+
+            if let Some(suggestion) = extending {
+                let span = suggestion.span;
+                let mut hint = suggestion.value[span.end - span.start..].to_string();
+                self.current_hint = hint.clone();
+
+                if self.description {
+                    if let Some(description) = &suggestion.description {
+                        hint = format!("{}  {}", hint, description);
+                    }
+                }
 
                 output = self.style.paint(hint).to_string();
             }
@@ -42,6 +74,14 @@ impl Hinter for DefaultHinter {
 
         output
     }
+
+    fn current_hint(&self) -> String {
+        self.current_hint.clone()
+    }
+
+    fn is_pending(&self) -> bool {
+        matches!(&self.completer, Some(completer) if completer.is_pending())
+    }
 }
 
 impl Default for DefaultHinter {
@@ -51,6 +91,8 @@ impl Default for DefaultHinter {
             history: false,
             style: Style::new().fg(Color::LightGray),
             inside_line: false,
+            description: false,
+            current_hint: String::new(),
         }
     }
 }
@@ -64,11 +106,27 @@ impl DefaultHinter {
 
     /// A builder that will configure the completer used by this hinter
     pub fn with_completer(mut self, completer: Box<dyn Completer>) -> DefaultHinter {
+        self.completer = Some(Box::new(CompleterAdapter::new(completer)));
+        self
+    }
+
+    /// A builder that configures a completer returning [`Suggestion`](crate::Suggestion)s
+    pub fn with_suggestion_completer(
+        mut self,
+        completer: Box<dyn SuggestionCompleter>,
+    ) -> DefaultHinter {
         self.completer = Some(completer);
         self
     }
 
-    /// A builder that configures the history the hinter will use to hint, if in history mode
+    /// A builder that shows the description of the hinted suggestion after the hint
+    pub fn with_description(mut self) -> DefaultHinter {
+        self.description = true;
+        self
+    }
+
+    /// A builder that hints the most recent history entry starting with the text before the
+    /// cursor, if no completer is configured
     pub fn with_history(mut self) -> DefaultHinter {
         self.history = true;
         self
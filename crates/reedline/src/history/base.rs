@@ -1,4 +1,6 @@
-use std::collections::vec_deque::Iter;
+use std::{collections::vec_deque::Iter, time::Duration};
+
+use chrono::{DateTime, Utc};
 
 use crate::line_buffer::LineBuffer;
 
@@ -11,11 +13,53 @@ pub enum HistoryNavigationQuery {
     // Fuzzy Search
 }
 
+/// Context of a command stored along with it by histories that support it
+///
+/// Fields that are `None` are unknown, or left unchanged when updating an entry with
+/// [`Reedline::update_last_history_metadata`](crate::Reedline::update_last_history_metadata).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryMetadata {
+    /// When the command was entered
+    pub timestamp: Option<DateTime<Utc>>,
+    /// The working directory the command was run in
+    pub cwd: Option<String>,
+    /// The host the command was run on
+    pub hostname: Option<String>,
+    /// The shell session the command was entered in
+    pub session_id: Option<i64>,
+    /// The exit status of the command
+    pub exit_status: Option<i64>,
+    /// How long the command took to run
+    pub duration: Option<Duration>,
+}
+
 pub trait HistoryAppender {
     // append any given string (a command) into the history - store
     fn append(&mut self, entry: String);
 
     fn iter_chronologic(&self) -> Iter<'_, String>;
+
+    // Attach the context of the most recent entry once it is known, like its exit status after
+    // the command finished. The set fields replace the stored ones.
+    // - Results in a no-op for histories that don't store any metadata
+    fn update_last_metadata(&mut self, _metadata: HistoryMetadata) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    // Errors of writes done along the way, like syncing a shared history file on append, since
+    // the last call
+    // - Results in no errors for histories that only write when asked to
+    fn take_errors(&mut self) -> Vec<std::io::Error> {
+        Vec::new()
+    }
+
+    // The most recent entry that starts with `prefix` and continues past it, used for hints
+    fn newest_with_prefix(&self, prefix: &str) -> Option<&str> {
+        self.iter_chronologic()
+            .rev()
+            .find(|entry| entry.len() > prefix.len() && entry.starts_with(prefix))
+            .map(String::as_str)
+    }
 }
 
 pub trait HistoryView {
@@ -30,6 +74,11 @@ pub trait HistoryView {
     // Returns the string (if present) at the cursor
     fn string_at_cursor(&self) -> Option<String>;
 
+    // Returns the metadata (if stored) of the entry at the cursor
+    fn metadata_at_cursor(&self) -> Option<HistoryMetadata> {
+        None
+    }
+
     // This will set a new navigation setup and based on input query
     fn set_navigation(&mut self, navigation: HistoryNavigationQuery);
 
@@ -1,35 +1,88 @@
 use std::{
-    collections::{vec_deque::Iter, VecDeque},
-    fs::{File, OpenOptions},
-    io::{BufRead, BufReader, BufWriter, Write},
-    path::PathBuf,
+    collections::{vec_deque::Iter, HashSet, VecDeque},
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
 };
 
+use fs2::FileExt;
+use glob::Pattern;
+use regex::Regex;
+
 use crate::line_buffer::LineBuffer;
 
 use super::{
     base::{HistoryAppender, HistoryNavigationQuery, HistoryView},
+    navigation::{cursor_back, cursor_forward},
+    prefix_index::PrefixIndex,
     History,
 };
 
 /// Default size of the [`FileBackedHistory`] used when calling [`FileBackedHistory::default()`]
 pub const HISTORY_SIZE: usize = 1000;
 
+// First line of history files with escaped entries, files without it have one plain entry
+// per line
+const FILE_HEADER: &str = "#reedline-history v2";
+
 /// Stateful history that allows up/down-arrow browsing with an internal cursor.
 ///
 /// Can optionally be associated with a newline separated history file using the [`FileBackedHistory::with_file()`] constructor.
+/// Newlines and backslashes within entries are escaped, so multiline entries stay intact.
 /// Similar to bash's behavior without HISTTIMEFORMAT.
 /// (See <https://www.gnu.org/software/bash/manual/html_node/Bash-History-Facilities.html>)
 /// If the history is associated to a file all new changes within a given history capacity will be written to disk when History is dropped.
+/// Several sessions can use the same file: the new entries are merged with the ones the other
+/// sessions wrote in the meantime, see [`FileBackedHistory::flush()`].
+///
+/// Which entries are kept can be configured like bash's HISTCONTROL and HISTIGNORE, e.g. to
+/// keep secrets out of the file:
+/// ```rust
+/// use reedline::FileBackedHistory;
+///
+/// let history = FileBackedHistory::default()
+///     .with_erase_duplicates()
+///     .with_ignore_space()
+///     .with_ignore_glob("*password*")
+///     .expect("Invalid pattern")
+///     .with_filter(Box::new(|entry| entry.len() > 2));
+/// ```
 #[derive(Debug)]
 pub struct FileBackedHistory {
     capacity: usize,
     entries: VecDeque<String>,
     cursor: usize, // If cursor == entries.len() outside history browsing
     file: Option<PathBuf>,
-    len_on_disk: usize,  // Keep track what was previously written to disk
-    truncate_file: bool, // as long as the file would not exceed capacity we can use appending writes
+    len_on_disk: usize, // Keep track what was previously written to disk
+    shared: bool,       // Exchange entries with other sessions while running
     query: HistoryNavigationQuery,
+    index: PrefixIndex,
+    seqs: VecDeque<usize>, // Sequence number of every entry in the prefix index, ascending
+    next_seq: usize,
+    erase_duplicates: bool,
+    ignore_space: bool,
+    filters: Vec<EntryFilter>, // An entry is only appended if all filters keep it
+    warnings: Vec<String>,     // Problems found while loading the file
+    synced: Option<FileStamp>, // The file as of the last read or write
+    errors: Vec<Error>,        // Failed writes of a shared history, until they are taken
+}
+
+/// Changes when the history file is replaced, so an unchanged file isn't read again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+/// Decides whether an entry is appended to the history
+struct EntryFilter(Box<dyn Fn(&str) -> bool>);
+
+impl fmt::Debug for EntryFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EntryFilter")
+    }
 }
 
 impl Default for FileBackedHistory {
@@ -44,7 +97,8 @@ impl Default for FileBackedHistory {
 impl History for FileBackedHistory {}
 
 impl HistoryAppender for FileBackedHistory {
-    /// Appends an entry if non-empty and not repetition of the previous entry.
+    /// Appends an entry if non-empty, not repetition of the previous entry and not ignored
+    /// by the configured filters.
     /// Resets the browsing cursor to the default state in front of the most recent entry.
     ///
     fn append(&mut self, entry: String) {
@@ -54,15 +108,28 @@ impl HistoryAppender for FileBackedHistory {
             .back()
             .map_or(true, |previous| previous != &entry)
             && !entry.is_empty()
+            && self.keeps(&entry)
         {
+            if self.erase_duplicates {
+                self.erase(&entry);
+            }
             if self.entries.len() == self.capacity {
                 // History is "full", so we delete the oldest entry first,
                 // before adding a new one.
-                self.entries.pop_front();
+                if let (Some(oldest), Some(seq)) = (self.entries.pop_front(), self.seqs.pop_front())
+                {
+                    self.index.remove(&oldest, seq);
+                }
                 self.len_on_disk = self.len_on_disk.saturating_sub(1);
-                self.truncate_file = true;
             }
+            self.index.insert(&entry, self.next_seq);
             self.entries.push_back(entry);
+            self.seqs.push_back(self.next_seq);
+            self.next_seq += 1;
+
+            if self.shared {
+                self.sync();
+            }
         }
         self.reset_cursor()
     }
@@ -70,39 +137,33 @@ impl HistoryAppender for FileBackedHistory {
     fn iter_chronologic(&self) -> Iter<'_, String> {
         self.entries.iter()
     }
+
+    fn take_errors(&mut self) -> Vec<Error> {
+        FileBackedHistory::take_errors(self)
+    }
+
+    /// Looks the entry up in the prefix index, which only visits the entries starting with
+    /// `prefix`
+    fn newest_with_prefix(&self, prefix: &str) -> Option<&str> {
+        if prefix.is_empty() {
+            return self.entries.back().map(String::as_str);
+        }
+
+        self.index
+            .candidates(prefix)
+            .filter_map(|seq| self.position(seq).map(|position| &self.entries[position]))
+            .find(|entry| entry.len() > prefix.len() && entry.starts_with(prefix))
+            .map(String::as_str)
+    }
 }
 
 impl HistoryView for FileBackedHistory {
     fn back(&mut self) {
-        match self.query.clone() {
-            HistoryNavigationQuery::Normal(_) => {
-                if self.cursor > 0 {
-                    self.cursor -= 1;
-                }
-            }
-            HistoryNavigationQuery::PrefixSearch(prefix) => {
-                self.back_with_criteria(&|entry| entry.starts_with(&prefix))
-            }
-            HistoryNavigationQuery::SubstringSearch(substring) => {
-                self.back_with_criteria(&|entry| entry.contains(&substring))
-            }
-        }
+        self.cursor = cursor_back(&self.entries, self.cursor, &self.query);
     }
 
     fn forward(&mut self) {
-        match self.query.clone() {
-            HistoryNavigationQuery::Normal(_) => {
-                if self.cursor < self.entries.len() {
-                    self.cursor += 1;
-                }
-            }
-            HistoryNavigationQuery::PrefixSearch(prefix) => {
-                self.forward_with_criteria(&|entry| entry.starts_with(&prefix))
-            }
-            HistoryNavigationQuery::SubstringSearch(substring) => {
-                self.forward_with_criteria(&|entry| entry.contains(&substring))
-            }
-        }
+        self.cursor = cursor_forward(&self.entries, self.cursor, &self.query);
     }
 
     fn string_at_cursor(&self) -> Option<String> {
@@ -110,6 +171,10 @@ impl HistoryView for FileBackedHistory {
     }
 
     fn set_navigation(&mut self, navigation: HistoryNavigationQuery) {
+        if self.shared {
+            // Browse the entries of the other sessions as well
+            self.sync();
+        }
         self.query = navigation;
         self.reset_cursor();
     }
@@ -131,16 +196,30 @@ impl FileBackedHistory {
             cursor: 0,
             file: None,
             len_on_disk: 0,
-            truncate_file: true,
+            shared: false,
             query: HistoryNavigationQuery::Normal(LineBuffer::default()),
+            index: PrefixIndex::default(),
+            seqs: VecDeque::with_capacity(capacity),
+            next_seq: 0,
+            erase_duplicates: false,
+            ignore_space: false,
+            filters: Vec::new(),
+            warnings: Vec::new(),
+            synced: None,
+            errors: Vec::new(),
         }
     }
 
     /// Creates a new history with an associated history file.
     ///
-    /// History file format: commands separated by new lines.
+    /// History file format: a header line followed by one command per line, with newlines
+    /// and backslashes escaped as `\n` and `\\`. Files without the header are read as one
+    /// plain command per line and converted when they are written the next time.
     /// If file exists file will be read otherwise empty file will be created.
     ///
+    /// Lines that are not valid UTF-8 are recovered by replacing the invalid bytes, see
+    /// [`FileBackedHistory::warnings()`].
+    ///
     ///
     /// **Side effects:** creates all nested directories to the file
     ///
@@ -154,110 +233,207 @@ impl FileBackedHistory {
         Ok(hist)
     }
 
-    /// Loads history from the associated newline separated file
+    /// A builder that shares the history with the other sessions using the same file.
+    ///
+    /// New entries are written to the file right away, and the entries of the other sessions
+    /// become available when browsing the history starts.
+    pub fn with_shared_history(mut self) -> Self {
+        self.shared = true;
+        self
+    }
+
+    /// A builder that removes the older occurrences of an entry when it is appended again,
+    /// like bash's `HISTCONTROL=erasedups`
+    pub fn with_erase_duplicates(mut self) -> Self {
+        self.erase_duplicates = true;
+        self
+    }
+
+    /// A builder that ignores entries starting with a space, like bash's
+    /// `HISTCONTROL=ignorespace`
+    pub fn with_ignore_space(mut self) -> Self {
+        self.ignore_space = true;
+        self
+    }
+
+    /// A builder that ignores entries matching the regular expression `pattern` anywhere
+    ///
+    /// Fails with [`ErrorKind::InvalidInput`] if the pattern is invalid.
+    pub fn with_ignore_regex(self, pattern: &str) -> std::io::Result<Self> {
+        let regex = Regex::new(pattern).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        Ok(self.with_filter(Box::new(move |entry| !regex.is_match(entry))))
+    }
+
+    /// A builder that ignores entries matching the glob `pattern` as a whole, like bash's
+    /// `HISTIGNORE`, e.g. `*password*`
+    ///
+    /// Fails with [`ErrorKind::InvalidInput`] if the pattern is invalid.
+    pub fn with_ignore_glob(self, pattern: &str) -> std::io::Result<Self> {
+        let glob = Pattern::new(pattern).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        Ok(self.with_filter(Box::new(move |entry| !glob.matches(entry))))
+    }
+
+    /// A builder that only appends the entries `filter` returns `true` for
+    ///
+    /// Can be used several times, an entry has to pass all filters.
+    pub fn with_filter(mut self, filter: Box<dyn Fn(&str) -> bool>) -> Self {
+        self.filters.push(EntryFilter(filter));
+        self
+    }
+
+    /// Problems found while loading the history file, like lines that are not valid UTF-8.
+    /// Loading recovers from them, the host can report them to the user.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Whether the entry passes the configured filters
+    fn keeps(&self, entry: &str) -> bool {
+        !(self.ignore_space && entry.starts_with(' '))
+            && self.filters.iter().all(|filter| (filter.0)(entry))
+    }
+
+    /// Removes all occurrences of `entry`, found by their sequence numbers in the prefix index
+    fn erase(&mut self, entry: &str) {
+        let seqs: Vec<usize> = self.index.occurrences(entry).collect();
+        for seq in seqs {
+            if let Some(position) = self.position(seq) {
+                self.entries.remove(position);
+                self.seqs.remove(position);
+                self.index.remove(entry, seq);
+                // The ones already written stay in the file until the next flush removes them
+                if position < self.len_on_disk {
+                    self.len_on_disk -= 1;
+                }
+            }
+        }
+    }
+
+    /// Position in `entries` of the entry with sequence number `seq`
+    fn position(&self, seq: usize) -> Option<usize> {
+        self.seqs.binary_search(&seq).ok()
+    }
+
+    /// Loads history from the associated history file
     ///
     /// Expects the [`History`] to be empty.
     ///
     ///
     /// **Side effect:** creates not yet existing file.
     fn load_file(&mut self) -> std::io::Result<()> {
-        let f = File::open(
-            self.file
-                .as_ref()
-                .expect("History::load_file should only be called if a filename is set"),
-        );
+        let file = self
+            .file
+            .clone()
+            .expect("History::load_file should only be called if a filename is set");
         assert!(
             self.entries.is_empty(),
             "History currently designed to load file once in the constructor"
         );
-        match f {
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::NotFound => {
-                    File::create(self.file.as_ref().unwrap())?;
-                    Ok(())
-                }
-                _ => Err(e),
-            },
-            Ok(file) => {
-                let reader = BufReader::new(file);
-                let mut from_file: VecDeque<String> = reader.lines().map(Result::unwrap).collect();
-                let from_file = if from_file.len() > self.capacity {
-                    from_file.split_off(from_file.len() - self.capacity)
-                } else {
-                    from_file
-                };
-                self.len_on_disk = from_file.len();
-                self.entries = from_file;
-                self.reset_cursor();
-                Ok(())
-            }
+        if !file.exists() {
+            File::create(&file)?;
         }
+        self.synced = stamp(&file);
+        let (from_file, invalid_lines) = read_history(&file)?;
+        self.warnings = invalid_lines
+            .into_iter()
+            .map(|line| {
+                format!(
+                    "Line {} of the history file {} is not valid UTF-8, \
+                     the invalid bytes were replaced",
+                    line,
+                    file.display()
+                )
+            })
+            .collect();
+        self.replace_entries(from_file);
+        Ok(())
     }
 
-    fn back_with_criteria(&mut self, criteria: &dyn Fn(&str) -> bool) {
-        if !self.entries.is_empty() {
-            let previous_match = self.entries.get(self.cursor);
-            if let Some((next_cursor, _)) = self
-                .entries
-                .iter()
-                .take(self.cursor)
-                .enumerate()
-                .rev()
-                .find(|(_, entry)| criteria(entry) && previous_match != Some(entry))
-            {
-                // set to entry
-                self.cursor = next_cursor
+    /// Writes unwritten history contents to disk.
+    ///
+    /// The new entries are appended to the current content of the file, so entries other
+    /// sessions wrote in the meantime are kept. The file is locked while it is merged and then
+    /// atomically replaced. With [`FileBackedHistory::with_shared_history()`] the entries of the
+    /// other sessions are also taken over for browsing. Without new entries the file is only
+    /// read again if it changed since the last flush.
+    ///
+    /// If file would exceed `capacity` truncates the oldest entries.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        let file = match &self.file {
+            Some(file) => file.clone(),
+            None => return Ok(()),
+        };
+
+        if self.len_on_disk < self.entries.len() {
+            let lock = lock_file(&file)?;
+            // Always read under the lock, another session may have replaced the file without
+            // changing its modification time or length
+            let mut merged = read_entries(&file)?;
+            merged.extend(self.entries.range(self.len_on_disk..).cloned());
+            if self.erase_duplicates {
+                merged = erase_older_duplicates(merged);
+            }
+            if merged.len() > self.capacity {
+                merged = merged.split_off(merged.len() - self.capacity);
             }
+            write_entries(&file, &merged)?;
+            self.synced = stamp(&file);
+            lock.unlock()?;
+
+            if self.shared && merged != self.entries {
+                self.replace_entries(merged);
+            } else {
+                self.len_on_disk = self.entries.len();
+            }
+        } else if self.shared && !self.is_synced(&file) {
+            // Stamped before reading, a file replaced in between is read again next time
+            self.synced = stamp(&file);
+            // The file is replaced atomically, so it can be read without the lock
+            let entries = read_entries(&file)?;
+            self.replace_entries(entries);
         }
+
+        Ok(())
     }
 
-    fn forward_with_criteria(&mut self, criteria: &dyn Fn(&str) -> bool) {
-        let previous_match = self.entries.get(self.cursor);
-        if let Some((next_cursor, _)) = self
-            .entries
-            .iter()
-            .enumerate()
-            .skip(self.cursor + 1)
-            .find(|(_, entry)| criteria(entry) && previous_match != Some(entry))
-        {
-            // set to entry
-            self.cursor = next_cursor
-        } else {
-            self.reset_cursor()
-        }
+    /// Errors of the flushes a shared history does when entries are appended or browsing
+    /// starts, since the last call. The entries stay in memory and are written by the next
+    /// successful flush.
+    pub fn take_errors(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.errors)
     }
 
-    /// Writes unwritten history contents to disk.
-    ///
-    /// If file would exceed `capacity` truncates the oldest entries.
-    fn flush(&mut self) -> std::io::Result<()> {
-        if self.file.is_none() {
-            return Ok(());
+    /// Flush and keep the error for [`FileBackedHistory::take_errors()`]
+    fn sync(&mut self) {
+        if let Err(e) = self.flush() {
+            self.errors.push(e);
         }
-        let file = if self.truncate_file {
-            // Rewrite the whole file if we truncated the old output
-            self.len_on_disk = 0;
-            // TODO: make this file race safe if multiple instances are used.
-            OpenOptions::new()
-                .write(true)
-                .truncate(true)
-                .open(self.file.as_ref().unwrap())?
-        } else {
-            // If the file is not beyond capacity just append new stuff
-            // (use the stored self.len_on_disk as offset)
-            OpenOptions::new()
-                .append(true)
-                .open(self.file.as_ref().unwrap())?
-        };
-        let mut writer = BufWriter::new(file);
-        for line in self.entries.range(self.len_on_disk..) {
-            writer.write_all(line.as_bytes())?;
-            writer.write_all("\n".as_bytes())?;
+    }
+
+    /// Whether the file is unchanged since it was last read or written
+    fn is_synced(&self, file: &Path) -> bool {
+        self.synced.is_some() && self.synced == stamp(file)
+    }
+
+    /// Take over the entries of the file, up to `capacity` of the most recent ones
+    fn replace_entries(&mut self, mut entries: VecDeque<String>) {
+        if entries.len() > self.capacity {
+            entries = entries.split_off(entries.len() - self.capacity);
         }
-        writer.flush()?;
-        self.len_on_disk = self.entries.len();
+        self.len_on_disk = entries.len();
+        self.entries = entries;
+        self.rebuild_index();
+        self.reset_cursor();
+    }
 
-        Ok(())
+    /// Index all entries again, after entries were replaced wholesale
+    fn rebuild_index(&mut self) {
+        self.index.clear();
+        for (seq, entry) in self.entries.iter().enumerate() {
+            self.index.insert(entry, seq);
+        }
+        self.seqs = (0..self.entries.len()).collect();
+        self.next_seq = self.entries.len();
     }
 
     /// Reset the internal browsing cursor
@@ -266,6 +442,143 @@ impl FileBackedHistory {
     }
 }
 
+/// Keeps only the most recent occurrence of every entry
+fn erase_older_duplicates(entries: VecDeque<String>) -> VecDeque<String> {
+    let mut seen = HashSet::new();
+    let mut unique = VecDeque::with_capacity(entries.len());
+    for entry in entries.into_iter().rev() {
+        if seen.insert(entry.clone()) {
+            unique.push_front(entry);
+        }
+    }
+    unique
+}
+
+/// The modification time and length of a file, `None` if it is missing
+fn stamp(file: &Path) -> Option<FileStamp> {
+    let metadata = fs::metadata(file).ok()?;
+    Some(FileStamp {
+        modified: metadata.modified().ok()?,
+        len: metadata.len(),
+    })
+}
+
+/// Reads the entries of a history file, a missing file has none
+fn read_entries(file: &Path) -> std::io::Result<VecDeque<String>> {
+    read_history(file).map(|(entries, _)| entries)
+}
+
+/// Reads the entries of a history file, along with the numbers of the lines that were not
+/// valid UTF-8 and had their invalid bytes replaced
+fn read_history(file: &Path) -> std::io::Result<(VecDeque<String>, Vec<usize>)> {
+    let file = match File::open(file) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok((VecDeque::new(), Vec::new()))
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut entries = VecDeque::new();
+    let mut invalid_lines = Vec::new();
+    let mut escaped = false;
+    for (index, line) in BufReader::new(file).split(b'\n').enumerate() {
+        let mut line = line?;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        let line = String::from_utf8(line).unwrap_or_else(|e| {
+            invalid_lines.push(index + 1);
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        });
+
+        if index == 0 && line == FILE_HEADER {
+            escaped = true;
+        } else if escaped {
+            entries.push_back(unescape(&line));
+        } else {
+            entries.push_back(line);
+        }
+    }
+
+    Ok((entries, invalid_lines))
+}
+
+/// Replaces the history file by writing a temporary file next to it and renaming it, so
+/// readers never see a partially written file
+fn write_entries(file: &Path, entries: &VecDeque<String>) -> std::io::Result<()> {
+    let temp_file = sibling(file, "tmp");
+    let mut writer = BufWriter::new(File::create(&temp_file)?);
+    writer.write_all(FILE_HEADER.as_bytes())?;
+    writer.write_all("\n".as_bytes())?;
+    for line in entries {
+        writer.write_all(escape(line).as_bytes())?;
+        writer.write_all("\n".as_bytes())?;
+    }
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    fs::rename(temp_file, file)
+}
+
+/// Escapes the line breaks of an entry to store it on one line
+fn escape(entry: &str) -> String {
+    let mut escaped = String::with_capacity(entry.len());
+    for c in entry.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverts [`escape()`], unknown escapes are kept as they are
+fn unescape(line: &str) -> String {
+    let mut entry = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            entry.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => entry.push('\\'),
+            Some('n') => entry.push('\n'),
+            Some('r') => entry.push('\r'),
+            Some(other) => {
+                entry.push('\\');
+                entry.push(other);
+            }
+            None => entry.push('\\'),
+        }
+    }
+    entry
+}
+
+/// Locks the history file exclusively, until the returned file is unlocked or closed.
+///
+/// The lock is taken on a separate `.lock` file, the history file itself is replaced while
+/// the lock is held.
+fn lock_file(file: &Path) -> std::io::Result<File> {
+    let lock = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(sibling(file, "lock"))?;
+    lock.lock_exclusive()?;
+    Ok(lock)
+}
+
+/// The path of `file` with `extension` appended
+fn sibling(file: &Path, extension: &str) -> PathBuf {
+    let mut path = file.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
 impl Drop for FileBackedHistory {
     /// On drop the content of the [`History`] will be written to the file if specified via [`FileBackedHistory::with_file()`].
     fn drop(&mut self) {
@@ -342,6 +655,78 @@ mod tests {
         assert_eq!(hist.entries.len(), 0);
     }
 
+    #[test]
+    fn erases_older_duplicates() {
+        let mut hist = FileBackedHistory::default().with_erase_duplicates();
+        hist.append("git status".to_string());
+        hist.append("ls".to_string());
+        hist.append("git log".to_string());
+        hist.append("git status".to_string());
+
+        let entries: Vec<&String> = hist.iter_chronologic().collect();
+        assert_eq!(entries, vec!["ls", "git log", "git status"]);
+        assert_eq!(hist.newest_with_prefix("git"), Some("git status"));
+        assert_eq!(hist.newest_with_prefix("l"), Some("ls"));
+    }
+
+    #[test]
+    fn erasing_keeps_the_prefix_index_in_step_with_a_full_history() {
+        let mut hist = FileBackedHistory::new(3).with_erase_duplicates();
+        for entry in &["git add", "git log", "git add", "ls", "git diff", "git add"] {
+            hist.append(entry.to_string());
+        }
+
+        let entries: Vec<&String> = hist.iter_chronologic().collect();
+        assert_eq!(entries, vec!["ls", "git diff", "git add"]);
+        assert_eq!(hist.newest_with_prefix("git"), Some("git add"));
+        assert_eq!(hist.newest_with_prefix("git d"), Some("git diff"));
+        assert_eq!(hist.newest_with_prefix("git l"), None);
+    }
+
+    #[test]
+    fn ignores_entries_starting_with_space() {
+        let mut hist = FileBackedHistory::default().with_ignore_space();
+        hist.append(" export TOKEN=secret".to_string());
+        hist.append("ls".to_string());
+
+        let entries: Vec<&String> = hist.iter_chronologic().collect();
+        assert_eq!(entries, vec!["ls"]);
+    }
+
+    #[test]
+    fn ignores_entries_matching_patterns_and_filters() {
+        let mut hist = FileBackedHistory::default()
+            .with_ignore_glob("*password*")
+            .unwrap()
+            .with_ignore_regex("^(ls|pwd)$")
+            .unwrap()
+            .with_filter(Box::new(|entry| !entry.starts_with("exit")));
+        for entry in &[
+            "set password=hunter2",
+            "ls",
+            "ls -la",
+            "pwd",
+            "exit 1",
+            "cd /",
+        ] {
+            hist.append(entry.to_string());
+        }
+
+        let entries: Vec<&String> = hist.iter_chronologic().collect();
+        assert_eq!(entries, vec!["ls -la", "cd /"]);
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        let error = FileBackedHistory::default()
+            .with_ignore_regex("(unclosed")
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(FileBackedHistory::default()
+            .with_ignore_glob("[unclosed")
+            .is_err());
+    }
+
     #[test]
     fn prefix_search_works() {
         let mut hist = FileBackedHistory::default();
@@ -357,6 +742,27 @@ mod tests {
         assert_eq!(hist.string_at_cursor(), Some("find me as well".to_string()));
     }
 
+    #[test]
+    fn substring_search_steps_back_and_forward() {
+        let mut hist = FileBackedHistory::default();
+        hist.append(String::from("cargo build"));
+        hist.append(String::from("git status"));
+        hist.append(String::from("cargo test"));
+        hist.append(String::from("git log"));
+
+        hist.set_navigation(HistoryNavigationQuery::SubstringSearch("rgo".to_string()));
+        hist.back();
+        assert_eq!(hist.string_at_cursor(), Some("cargo test".to_string()));
+        hist.back();
+        assert_eq!(hist.string_at_cursor(), Some("cargo build".to_string()));
+        hist.back();
+        assert_eq!(hist.string_at_cursor(), Some("cargo build".to_string()));
+        hist.forward();
+        assert_eq!(hist.string_at_cursor(), Some("cargo test".to_string()));
+        hist.forward();
+        assert_eq!(hist.string_at_cursor(), None);
+    }
+
     #[test]
     fn prefix_search_bottoms_out() {
         let mut hist = FileBackedHistory::default();
@@ -487,7 +893,276 @@ mod tests {
 
         let actual: Vec<String> = BufReader::new(f).lines().map(|x| x.unwrap()).collect();
 
-        assert_eq!(entries, actual);
+        assert_eq!(actual[0], FILE_HEADER);
+        assert_eq!(entries, actual[1..]);
+
+        tmp.close().unwrap();
+    }
+
+    #[test]
+    fn newest_with_prefix_prefers_recent_entries() {
+        let mut hist = FileBackedHistory::default();
+        hist.append(String::from("git status"));
+        hist.append(String::from("git log"));
+        hist.append(String::from("ls"));
+
+        assert_eq!(hist.newest_with_prefix("g"), Some("git log"));
+        assert_eq!(hist.newest_with_prefix("git s"), Some("git status"));
+        assert_eq!(hist.newest_with_prefix("ls"), None);
+        assert_eq!(hist.newest_with_prefix("cd"), None);
+        assert_eq!(hist.newest_with_prefix(""), Some("ls"));
+    }
+
+    #[test]
+    fn newest_with_prefix_forgets_truncated_entries() {
+        let mut hist = FileBackedHistory::new(2);
+        hist.append(String::from("git status"));
+        hist.append(String::from("ls"));
+        hist.append(String::from("git log"));
+        hist.append(String::from("cargo build"));
+
+        assert_eq!(hist.newest_with_prefix("git s"), None);
+        assert_eq!(hist.newest_with_prefix("git"), Some("git log"));
+        assert_eq!(hist.newest_with_prefix("l"), None);
+    }
+
+    #[test]
+    fn newest_with_prefix_searches_loaded_entries() {
+        use tempfile::tempdir;
+
+        let tmp = tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+        std::fs::write(&histfile, "git status\ngit log\nls\n").unwrap();
+
+        let hist = FileBackedHistory::with_file(2, histfile).unwrap();
+
+        assert_eq!(hist.newest_with_prefix("git"), Some("git log"));
+        assert_eq!(hist.newest_with_prefix("git s"), None);
+
+        tmp.close().unwrap();
+    }
+
+    // The entries in the history file, after its header
+    fn read_history_file(histfile: &std::path::Path) -> Vec<String> {
+        let f = File::open(histfile).unwrap();
+        let mut lines: Vec<String> = BufReader::new(f).lines().map(|x| x.unwrap()).collect();
+        assert_eq!(lines.remove(0), FILE_HEADER);
+        lines
+    }
+
+    #[test]
+    fn multiline_entries_survive_a_reload() {
+        use tempfile::tempdir;
+
+        let tmp = tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+        let entries = vec!["for x in 1..3 {\n  echo $x\n}", "echo a\\nb \\", "ls"];
+
+        {
+            let mut hist = FileBackedHistory::with_file(5, histfile.clone()).unwrap();
+            entries.iter().for_each(|e| hist.append(e.to_string()));
+        }
+
+        assert_eq!(
+            read_history_file(&histfile),
+            vec!["for x in 1..3 {\\n  echo $x\\n}", "echo a\\\\nb \\\\", "ls"]
+        );
+        let hist = FileBackedHistory::with_file(5, histfile).unwrap();
+        let actual: Vec<&String> = hist.iter_chronologic().collect();
+        assert_eq!(actual, entries);
+
+        tmp.close().unwrap();
+    }
+
+    #[test]
+    fn plain_files_are_read_and_converted() {
+        use tempfile::tempdir;
+
+        let tmp = tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+        std::fs::write(&histfile, "echo a\\nb\r\nls\n").unwrap();
+
+        {
+            let mut hist = FileBackedHistory::with_file(5, histfile.clone()).unwrap();
+            let actual: Vec<&String> = hist.iter_chronologic().collect();
+            assert_eq!(actual, vec!["echo a\\nb", "ls"]);
+            hist.append("pwd".to_string());
+        }
+
+        assert_eq!(
+            read_history_file(&histfile),
+            vec!["echo a\\\\nb", "ls", "pwd"]
+        );
+
+        tmp.close().unwrap();
+    }
+
+    #[test]
+    fn invalid_utf8_lines_are_recovered_with_a_warning() {
+        use tempfile::tempdir;
+
+        let tmp = tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+        std::fs::write(&histfile, b"ls\necho \xff\xfe\ncd /\n").unwrap();
+
+        let hist = FileBackedHistory::with_file(5, histfile).unwrap();
+
+        let actual: Vec<&String> = hist.iter_chronologic().collect();
+        assert_eq!(actual, vec!["ls", "echo \u{fffd}\u{fffd}", "cd /"]);
+        assert_eq!(hist.warnings().len(), 1);
+        assert!(hist.warnings()[0].starts_with("Line 2 of the history file"));
+
+        tmp.close().unwrap();
+    }
+
+    #[test]
+    fn erased_duplicates_are_removed_from_the_file() {
+        use tempfile::tempdir;
+
+        let tmp = tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+        std::fs::write(&histfile, "git status\nls\ngit status\ncargo build\n").unwrap();
+
+        {
+            let mut hist = FileBackedHistory::with_file(10, histfile.clone())
+                .unwrap()
+                .with_erase_duplicates();
+            hist.append("git status".to_string());
+        }
+
+        assert_eq!(
+            read_history_file(&histfile),
+            vec!["ls", "cargo build", "git status"]
+        );
+
+        tmp.close().unwrap();
+    }
+
+    #[test]
+    fn concurrent_sessions_keep_each_others_entries() {
+        use tempfile::tempdir;
+
+        let tmp = tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+        std::fs::write(&histfile, "old\n").unwrap();
+
+        let mut first = FileBackedHistory::with_file(5, histfile.clone()).unwrap();
+        let mut second = FileBackedHistory::with_file(5, histfile.clone()).unwrap();
+        first.append("first 1".to_string());
+        second.append("second 1".to_string());
+        first.append("first 2".to_string());
+
+        first.flush().unwrap();
+        drop(second);
+        drop(first);
+
+        assert_eq!(
+            read_history_file(&histfile),
+            vec!["old", "first 1", "first 2", "second 1"]
+        );
+
+        tmp.close().unwrap();
+    }
+
+    #[test]
+    fn merged_file_is_truncated_to_capacity() {
+        use tempfile::tempdir;
+
+        let tmp = tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        let mut first = FileBackedHistory::with_file(3, histfile.clone()).unwrap();
+        let mut second = FileBackedHistory::with_file(3, histfile.clone()).unwrap();
+        first.append("a".to_string());
+        first.append("b".to_string());
+        second.append("c".to_string());
+        second.append("d".to_string());
+        drop(first);
+        drop(second);
+
+        assert_eq!(read_history_file(&histfile), vec!["b", "c", "d"]);
+
+        tmp.close().unwrap();
+    }
+
+    #[test]
+    fn shared_history_picks_up_entries_of_other_sessions() {
+        use tempfile::tempdir;
+
+        let tmp = tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        let mut first = FileBackedHistory::with_file(5, histfile.clone())
+            .unwrap()
+            .with_shared_history();
+        let mut second = FileBackedHistory::with_file(5, histfile.clone())
+            .unwrap()
+            .with_shared_history();
+
+        first.append("from first".to_string());
+        second.append("from second".to_string());
+
+        first.set_navigation(HistoryNavigationQuery::Normal(LineBuffer::default()));
+        first.back();
+        assert_eq!(first.string_at_cursor(), Some("from second".to_string()));
+        first.back();
+        assert_eq!(first.string_at_cursor(), Some("from first".to_string()));
+
+        assert_eq!(
+            read_history_file(&histfile),
+            vec!["from first", "from second"]
+        );
+
+        tmp.close().unwrap();
+    }
+
+    #[test]
+    fn shared_history_keeps_entries_of_a_replacement_it_did_not_notice() {
+        use tempfile::tempdir;
+
+        let tmp = tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        let mut first = FileBackedHistory::with_file(5, histfile.clone())
+            .unwrap()
+            .with_shared_history();
+        let mut second = FileBackedHistory::with_file(5, histfile.clone())
+            .unwrap()
+            .with_shared_history();
+
+        first.append("a".to_string());
+        second.append("b".to_string());
+        // Like a replacement in the same tick with the same length as the last write
+        first.synced = stamp(&histfile);
+        first.append("c".to_string());
+
+        assert_eq!(read_history_file(&histfile), vec!["a", "b", "c"]);
+
+        tmp.close().unwrap();
+    }
+
+    #[test]
+    fn failed_syncs_are_reported_and_retried() {
+        use tempfile::tempdir;
+
+        let tmp = tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+        let mut history = FileBackedHistory::with_file(5, histfile.clone())
+            .unwrap()
+            .with_shared_history();
+
+        // The file is replaced by renaming a temporary file, which can't be created now
+        std::fs::create_dir(sibling(&histfile, "tmp")).unwrap();
+        history.append("unsaved".to_string());
+
+        assert_eq!(history.take_errors().len(), 1);
+        assert!(history.take_errors().is_empty());
+
+        std::fs::remove_dir(sibling(&histfile, "tmp")).unwrap();
+        history.append("saved".to_string());
+
+        assert!(history.take_errors().is_empty());
+        assert_eq!(read_history_file(&histfile), vec!["unsaved", "saved"]);
 
         tmp.close().unwrap();
     }
@@ -1,5 +1,11 @@
 mod base;
 mod file_backed;
+mod navigation;
+mod prefix_index;
+#[cfg(feature = "sqlite")]
+mod sqlite_backed;
 
-pub use base::{History, HistoryNavigationQuery};
+pub use base::{History, HistoryMetadata, HistoryNavigationQuery};
 pub use file_backed::{FileBackedHistory, HISTORY_SIZE};
+#[cfg(feature = "sqlite")]
+pub use sqlite_backed::SqliteBackedHistory;
@@ -0,0 +1,69 @@
+use std::collections::VecDeque;
+
+use super::HistoryNavigationQuery;
+
+// Browsing logic shared by the in-memory entries of the history implementations.
+// The cursor is an index into `entries`, `entries.len()` when not browsing.
+
+/// The cursor of the previous entry matching the query, unchanged if there is none
+pub(super) fn cursor_back(
+    entries: &VecDeque<String>,
+    cursor: usize,
+    query: &HistoryNavigationQuery,
+) -> usize {
+    match query {
+        HistoryNavigationQuery::Normal(_) => cursor.saturating_sub(1),
+        HistoryNavigationQuery::PrefixSearch(prefix) => {
+            back_with_criteria(entries, cursor, &|entry| entry.starts_with(prefix))
+        }
+        HistoryNavigationQuery::SubstringSearch(substring) => {
+            back_with_criteria(entries, cursor, &|entry| entry.contains(substring))
+        }
+    }
+}
+
+/// The cursor of the next entry matching the query, past the newest entry if there is none
+pub(super) fn cursor_forward(
+    entries: &VecDeque<String>,
+    cursor: usize,
+    query: &HistoryNavigationQuery,
+) -> usize {
+    match query {
+        HistoryNavigationQuery::Normal(_) => (cursor + 1).min(entries.len()),
+        HistoryNavigationQuery::PrefixSearch(prefix) => {
+            forward_with_criteria(entries, cursor, &|entry| entry.starts_with(prefix))
+        }
+        HistoryNavigationQuery::SubstringSearch(substring) => {
+            forward_with_criteria(entries, cursor, &|entry| entry.contains(substring))
+        }
+    }
+}
+
+fn back_with_criteria(
+    entries: &VecDeque<String>,
+    cursor: usize,
+    criteria: &dyn Fn(&str) -> bool,
+) -> usize {
+    let previous_match = entries.get(cursor);
+    entries
+        .iter()
+        .take(cursor)
+        .enumerate()
+        .rev()
+        .find(|(_, entry)| criteria(entry) && previous_match != Some(entry))
+        .map_or(cursor, |(next_cursor, _)| next_cursor)
+}
+
+fn forward_with_criteria(
+    entries: &VecDeque<String>,
+    cursor: usize,
+    criteria: &dyn Fn(&str) -> bool,
+) -> usize {
+    let previous_match = entries.get(cursor);
+    entries
+        .iter()
+        .enumerate()
+        .skip(cursor + 1)
+        .find(|(_, entry)| criteria(entry) && previous_match != Some(entry))
+        .map_or(entries.len(), |(next_cursor, _)| next_cursor)
+}
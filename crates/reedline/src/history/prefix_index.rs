@@ -0,0 +1,154 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    ops::Bound,
+};
+
+// Number of leading characters the entries are bucketed by
+const KEY_LEN: usize = 3;
+
+/// Index of history entries by their first characters, to find the newest entry with a given
+/// prefix without scanning the whole history.
+///
+/// Entries are identified by a sequence number that grows with every appended entry. Each
+/// entry is listed in the buckets of its first one, two and three characters, ordered from
+/// the oldest to the newest, so a prefix of up to three characters is answered by the end of
+/// its bucket. Longer prefixes are looked up as a range of the sorted entries, which only
+/// visits the entries starting with the prefix.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct PrefixIndex {
+    buckets: HashMap<String, VecDeque<usize>>,
+    // The sequence numbers of every entry, oldest first
+    entries: BTreeMap<String, Vec<usize>>,
+}
+
+impl PrefixIndex {
+    /// Add the entry with sequence number `seq`, which has to be the newest one
+    pub(crate) fn insert(&mut self, entry: &str, seq: usize) {
+        for key in keys(entry) {
+            self.buckets
+                .entry(key.to_string())
+                .or_default()
+                .push_back(seq);
+        }
+        self.entries.entry(entry.to_string()).or_default().push(seq);
+    }
+
+    /// Remove the entry with sequence number `seq`
+    pub(crate) fn remove(&mut self, entry: &str, seq: usize) {
+        for key in keys(entry) {
+            if let Some(bucket) = self.buckets.get_mut(key) {
+                // Usually the oldest entry is removed, which sits at the front of the bucket
+                if bucket.front() == Some(&seq) {
+                    bucket.pop_front();
+                } else if let Ok(index) = bucket.binary_search(&seq) {
+                    bucket.remove(index);
+                }
+                if bucket.is_empty() {
+                    self.buckets.remove(key);
+                }
+            }
+        }
+        if let Some(seqs) = self.entries.get_mut(entry) {
+            if let Ok(index) = seqs.binary_search(&seq) {
+                seqs.remove(index);
+            }
+            if seqs.is_empty() {
+                self.entries.remove(entry);
+            }
+        }
+    }
+
+    /// Sequence numbers of the occurrences of exactly `entry`, oldest first
+    pub(crate) fn occurrences<'a>(&'a self, entry: &str) -> impl Iterator<Item = usize> + 'a {
+        self.entries.get(entry).into_iter().flatten().copied()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.buckets.clear();
+        self.entries.clear();
+    }
+
+    /// Candidates for the newest entry that starts with `prefix` and continues past it, from
+    /// the newest to the oldest. The first candidate longer than the prefix is the one.
+    pub(crate) fn candidates<'a>(&'a self, prefix: &str) -> Box<dyn Iterator<Item = usize> + 'a> {
+        if prefix.chars().nth(KEY_LEN).is_none() {
+            return Box::new(
+                self.buckets
+                    .get(prefix)
+                    .into_iter()
+                    .flat_map(|bucket| bucket.iter().rev().copied()),
+            );
+        }
+
+        // Only the newest occurrence of each entry continuing the prefix is a contender
+        let newest = self
+            .entries
+            .range::<str, _>((Bound::Excluded(prefix), Bound::Unbounded))
+            .take_while(|(entry, _)| entry.starts_with(prefix))
+            .filter_map(|(_, seqs)| seqs.last().copied())
+            .max();
+        Box::new(newest.into_iter())
+    }
+}
+
+// The prefixes of up to `KEY_LEN` characters an entry is listed under
+fn keys(entry: &str) -> impl Iterator<Item = &str> {
+    entry
+        .char_indices()
+        .skip(1)
+        .map(|(index, _)| index)
+        .chain(std::iter::once(entry.len()))
+        .take(KEY_LEN)
+        .filter(|end| *end > 0)
+        .map(move |end| &entry[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn entries_are_listed_under_their_first_characters() {
+        assert_eq!(keys("git").collect::<Vec<_>>(), vec!["g", "gi", "git"]);
+        assert_eq!(keys("ls -la").collect::<Vec<_>>(), vec!["l", "ls", "ls "]);
+        assert_eq!(keys("äb").collect::<Vec<_>>(), vec!["ä", "äb"]);
+        assert_eq!(keys("").count(), 0);
+    }
+
+    #[test]
+    fn candidates_are_returned_newest_first() {
+        let mut index = PrefixIndex::default();
+        index.insert("git status", 0);
+        index.insert("ls", 1);
+        index.insert("git log", 2);
+        index.insert("gitk", 3);
+
+        assert_eq!(index.candidates("gi").collect::<Vec<_>>(), vec![3, 2, 0]);
+        assert_eq!(index.candidates("git ").collect::<Vec<_>>(), vec![2]);
+        assert_eq!(index.candidates("git").collect::<Vec<_>>(), vec![3, 2, 0]);
+        assert_eq!(index.candidates("git commit").count(), 0);
+
+        index.remove("git status", 0);
+        index.remove("git log", 2);
+        assert_eq!(index.candidates("git").collect::<Vec<_>>(), vec![3]);
+        assert_eq!(index.candidates("git ").count(), 0);
+        assert_eq!(index.candidates("cd").count(), 0);
+    }
+
+    #[test]
+    fn long_prefixes_find_the_newest_longer_entry() {
+        let mut index = PrefixIndex::default();
+        index.insert("cargo test", 0);
+        index.insert("cargo build", 1);
+        index.insert("cargo test --all", 2);
+        index.insert("cargo test", 3);
+
+        assert_eq!(index.candidates("cargo t").collect::<Vec<_>>(), vec![3]);
+        // The entry equal to the prefix doesn't continue it
+        assert_eq!(index.candidates("cargo test").collect::<Vec<_>>(), vec![2]);
+
+        index.remove("cargo test", 3);
+        assert_eq!(index.candidates("cargo t").collect::<Vec<_>>(), vec![2]);
+    }
+}
@@ -0,0 +1,426 @@
+use std::{
+    collections::{vec_deque::Iter, VecDeque},
+    env,
+    io::{Error, ErrorKind},
+    path::PathBuf,
+    time::Duration,
+};
+
+use chrono::{TimeZone, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::line_buffer::LineBuffer;
+
+use super::{
+    base::{HistoryAppender, HistoryMetadata, HistoryNavigationQuery, HistoryView},
+    navigation::{cursor_back, cursor_forward},
+    prefix_index::PrefixIndex,
+    History,
+};
+
+// How long a write waits for another session to release the database before it fails
+const BUSY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// History stored in an SQLite database, along with the context of every command.
+///
+/// Each entry records when, where and in which session it was entered. The host can attach
+/// the exit status and the duration once the command finished with
+/// [`Reedline::update_last_history_metadata()`](crate::Reedline::update_last_history_metadata).
+/// The database keeps every entry, the most recent `capacity` ones are loaded for browsing.
+///
+/// Requires the `sqlite` feature.
+pub struct SqliteBackedHistory {
+    connection: Connection,
+    capacity: usize,
+    entries: VecDeque<String>,
+    ids: VecDeque<Option<i64>>, // Row ids of the entries, `None` if storing the entry failed
+    cursor: usize,              // If cursor == entries.len() outside history browsing
+    query: HistoryNavigationQuery,
+    index: PrefixIndex,
+    first_seq: usize, // Sequence number of the oldest entry in the prefix index
+    session_id: i64,
+    hostname: Option<String>,
+    errors: Vec<Error>, // Failed inserts, until they are taken
+}
+
+impl History for SqliteBackedHistory {}
+
+impl HistoryAppender for SqliteBackedHistory {
+    /// Appends an entry if non-empty and not repetition of the previous entry.
+    /// The entry is stored with its timestamp, working directory, hostname and session, a
+    /// failure is kept for [`SqliteBackedHistory::take_errors()`].
+    /// Resets the browsing cursor to the default state in front of the most recent entry.
+    fn append(&mut self, entry: String) {
+        if self.entries.back() != Some(&entry) && !entry.is_empty() {
+            if self.entries.len() == self.capacity {
+                // Only the loaded entries are limited, the database keeps the oldest one
+                if let Some(oldest) = self.entries.pop_front() {
+                    self.ids.pop_front();
+                    self.index.remove(&oldest, self.first_seq);
+                    self.first_seq += 1;
+                }
+            }
+
+            let cwd = env::current_dir()
+                .ok()
+                .map(|cwd| cwd.to_string_lossy().to_string());
+            let inserted = self
+                .connection
+                .execute(
+                    "INSERT INTO history (command, timestamp, cwd, hostname, session_id)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        entry,
+                        Utc::now().timestamp_millis(),
+                        cwd,
+                        self.hostname,
+                        self.session_id
+                    ],
+                )
+                .map(|_| self.connection.last_insert_rowid());
+            let id = match inserted {
+                Ok(id) => Some(id),
+                Err(e) => {
+                    self.errors.push(io_error(e));
+                    None
+                }
+            };
+
+            self.index
+                .insert(&entry, self.first_seq + self.entries.len());
+            self.entries.push_back(entry);
+            self.ids.push_back(id);
+        }
+        self.reset_cursor()
+    }
+
+    fn iter_chronologic(&self) -> Iter<'_, String> {
+        self.entries.iter()
+    }
+
+    fn take_errors(&mut self) -> Vec<Error> {
+        SqliteBackedHistory::take_errors(self)
+    }
+
+    fn newest_with_prefix(&self, prefix: &str) -> Option<&str> {
+        if prefix.is_empty() {
+            return self.entries.back().map(String::as_str);
+        }
+
+        self.index
+            .candidates(prefix)
+            .filter_map(|seq| self.entries.get(seq - self.first_seq))
+            .find(|entry| entry.len() > prefix.len() && entry.starts_with(prefix))
+            .map(String::as_str)
+    }
+
+    fn update_last_metadata(&mut self, metadata: HistoryMetadata) -> std::io::Result<()> {
+        let id = match self.ids.back() {
+            Some(Some(id)) => *id,
+            Some(None) => {
+                return Err(Error::new(
+                    ErrorKind::NotFound,
+                    "The last history entry could not be stored",
+                ))
+            }
+            None => return Ok(()),
+        };
+
+        self.connection
+            .execute(
+                "UPDATE history SET
+                    timestamp = COALESCE(?1, timestamp),
+                    cwd = COALESCE(?2, cwd),
+                    hostname = COALESCE(?3, hostname),
+                    session_id = COALESCE(?4, session_id),
+                    exit_status = COALESCE(?5, exit_status),
+                    duration_ms = COALESCE(?6, duration_ms)
+                 WHERE id = ?7",
+                params![
+                    metadata
+                        .timestamp
+                        .map(|timestamp| timestamp.timestamp_millis()),
+                    metadata.cwd,
+                    metadata.hostname,
+                    metadata.session_id,
+                    metadata.exit_status,
+                    metadata
+                        .duration
+                        .map(|duration| duration.as_millis() as i64),
+                    id
+                ],
+            )
+            .map_err(io_error)?;
+
+        Ok(())
+    }
+}
+
+impl HistoryView for SqliteBackedHistory {
+    fn back(&mut self) {
+        self.cursor = cursor_back(&self.entries, self.cursor, &self.query);
+    }
+
+    fn forward(&mut self) {
+        self.cursor = cursor_forward(&self.entries, self.cursor, &self.query);
+    }
+
+    fn string_at_cursor(&self) -> Option<String> {
+        self.entries.get(self.cursor).cloned()
+    }
+
+    fn metadata_at_cursor(&self) -> Option<HistoryMetadata> {
+        let id = (*self.ids.get(self.cursor)?)?;
+
+        self.connection
+            .query_row(
+                "SELECT timestamp, cwd, hostname, session_id, exit_status, duration_ms
+                 FROM history WHERE id = ?1",
+                params![id],
+                |row| {
+                    Ok(HistoryMetadata {
+                        timestamp: row
+                            .get::<_, Option<i64>>(0)?
+                            .and_then(|millis| Utc.timestamp_millis_opt(millis).single()),
+                        cwd: row.get(1)?,
+                        hostname: row.get(2)?,
+                        session_id: row.get(3)?,
+                        exit_status: row.get(4)?,
+                        duration: row
+                            .get::<_, Option<i64>>(5)?
+                            .map(|millis| Duration::from_millis(millis as u64)),
+                    })
+                },
+            )
+            .optional()
+            .ok()
+            .flatten()
+    }
+
+    fn set_navigation(&mut self, navigation: HistoryNavigationQuery) {
+        self.query = navigation;
+        self.reset_cursor();
+    }
+
+    fn get_navigation(&self) -> HistoryNavigationQuery {
+        self.query.clone()
+    }
+}
+
+impl SqliteBackedHistory {
+    /// Opens the history database at `file`, creating it if it doesn't exist yet, and loads
+    /// the most recent `capacity` entries.
+    ///
+    /// A new session id is generated, see [`SqliteBackedHistory::with_session_id()`].
+    ///
+    /// **Side effects:** creates all nested directories to the file
+    pub fn with_file(capacity: usize, file: PathBuf) -> std::io::Result<Self> {
+        if let Some(base_dir) = file.parent() {
+            std::fs::create_dir_all(base_dir)?;
+        }
+        let connection = Connection::open(file).map_err(io_error)?;
+        connection.busy_timeout(BUSY_TIMEOUT).map_err(io_error)?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS history (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    command TEXT NOT NULL,
+                    timestamp INTEGER,
+                    cwd TEXT,
+                    hostname TEXT,
+                    session_id INTEGER,
+                    exit_status INTEGER,
+                    duration_ms INTEGER
+                );",
+            )
+            .map_err(io_error)?;
+
+        let mut hist = SqliteBackedHistory {
+            connection,
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+            ids: VecDeque::with_capacity(capacity),
+            cursor: 0,
+            query: HistoryNavigationQuery::Normal(LineBuffer::default()),
+            index: PrefixIndex::default(),
+            first_seq: 0,
+            session_id: Utc::now().timestamp_millis(),
+            hostname: gethostname::gethostname().into_string().ok(),
+            errors: Vec::new(),
+        };
+        hist.load_entries().map_err(io_error)?;
+        Ok(hist)
+    }
+
+    /// A builder that sets the id the new entries are stored with, to share one session
+    /// between several instances
+    pub fn with_session_id(mut self, session_id: i64) -> Self {
+        self.session_id = session_id;
+        self
+    }
+
+    /// The id of the session the new entries are stored with
+    pub fn session_id(&self) -> i64 {
+        self.session_id
+    }
+
+    /// Errors of the inserts done when entries were appended, since the last call. The
+    /// entries stay available for browsing, but are not in the database.
+    pub fn take_errors(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.errors)
+    }
+
+    fn load_entries(&mut self) -> rusqlite::Result<()> {
+        let rows = {
+            let mut statement = self
+                .connection
+                .prepare("SELECT id, command FROM history ORDER BY id DESC LIMIT ?1")?;
+            let rows = statement.query_map(params![self.capacity as i64], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+
+        for (id, command) in rows.into_iter().rev() {
+            self.index.insert(&command, self.entries.len());
+            self.ids.push_back(Some(id));
+            self.entries.push_back(command);
+        }
+        self.reset_cursor();
+
+        Ok(())
+    }
+
+    /// Reset the internal browsing cursor
+    fn reset_cursor(&mut self) {
+        self.cursor = self.entries.len();
+    }
+}
+
+fn io_error(error: rusqlite::Error) -> Error {
+    Error::new(ErrorKind::Other, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    #[test]
+    fn entries_are_kept_across_instances() {
+        let tmp = tempdir().unwrap();
+        let histfile = tmp.path().join("nested_path").join("history.sqlite3");
+
+        {
+            let mut hist = SqliteBackedHistory::with_file(5, histfile.clone()).unwrap();
+            hist.append("git status".to_string());
+            hist.append("git status".to_string());
+            hist.append("ls".to_string());
+            hist.append("cargo build".to_string());
+        }
+
+        let hist = SqliteBackedHistory::with_file(2, histfile).unwrap();
+        let entries: Vec<&String> = hist.iter_chronologic().collect();
+        assert_eq!(entries, vec!["ls", "cargo build"]);
+
+        tmp.close().unwrap();
+    }
+
+    #[test]
+    fn entries_are_stored_with_their_context() {
+        let tmp = tempdir().unwrap();
+        let mut hist = SqliteBackedHistory::with_file(5, tmp.path().join("history.sqlite3"))
+            .unwrap()
+            .with_session_id(42);
+
+        hist.append("cargo build".to_string());
+        hist.back();
+        let metadata = hist.metadata_at_cursor().unwrap();
+
+        assert!(metadata.timestamp.is_some());
+        assert_eq!(
+            metadata.cwd,
+            Some(env::current_dir().unwrap().to_string_lossy().to_string())
+        );
+        assert_eq!(metadata.session_id, Some(42));
+        assert_eq!(metadata.exit_status, None);
+        assert_eq!(metadata.duration, None);
+
+        tmp.close().unwrap();
+    }
+
+    #[test]
+    fn metadata_is_attached_to_the_last_entry() {
+        let tmp = tempdir().unwrap();
+        let mut hist =
+            SqliteBackedHistory::with_file(5, tmp.path().join("history.sqlite3")).unwrap();
+
+        hist.append("ls".to_string());
+        hist.append("cargo build".to_string());
+        hist.update_last_metadata(HistoryMetadata {
+            exit_status: Some(101),
+            duration: Some(Duration::from_millis(1500)),
+            cwd: Some("/tmp".to_string()),
+            ..HistoryMetadata::default()
+        })
+        .unwrap();
+
+        hist.back();
+        let metadata = hist.metadata_at_cursor().unwrap();
+        assert_eq!(metadata.exit_status, Some(101));
+        assert_eq!(metadata.duration, Some(Duration::from_millis(1500)));
+        assert_eq!(metadata.cwd, Some("/tmp".to_string()));
+        assert_eq!(metadata.session_id, Some(hist.session_id()));
+
+        hist.back();
+        assert_eq!(hist.string_at_cursor(), Some("ls".to_string()));
+        assert_eq!(hist.metadata_at_cursor().unwrap().exit_status, None);
+
+        tmp.close().unwrap();
+    }
+
+    #[test]
+    fn failed_inserts_are_reported() {
+        let tmp = tempdir().unwrap();
+        let histfile = tmp.path().join("history.sqlite3");
+        let mut hist = SqliteBackedHistory::with_file(5, histfile.clone()).unwrap();
+        Connection::open(histfile)
+            .unwrap()
+            .execute_batch("DROP TABLE history;")
+            .unwrap();
+
+        hist.append("ls".to_string());
+
+        assert_eq!(hist.take_errors().len(), 1);
+        assert!(hist.take_errors().is_empty());
+        assert!(hist
+            .update_last_metadata(HistoryMetadata {
+                exit_status: Some(0),
+                ..HistoryMetadata::default()
+            })
+            .is_err());
+        assert_eq!(hist.newest_with_prefix("l"), Some("ls"));
+
+        tmp.close().unwrap();
+    }
+
+    #[test]
+    fn prefix_search_works() {
+        let tmp = tempdir().unwrap();
+        let mut hist =
+            SqliteBackedHistory::with_file(5, tmp.path().join("history.sqlite3")).unwrap();
+        hist.append(String::from("find me as well"));
+        hist.append(String::from("test"));
+        hist.append(String::from("find me"));
+
+        hist.set_navigation(HistoryNavigationQuery::PrefixSearch("find".to_string()));
+        hist.back();
+        assert_eq!(hist.string_at_cursor(), Some("find me".to_string()));
+        hist.back();
+        assert_eq!(hist.string_at_cursor(), Some("find me as well".to_string()));
+        assert_eq!(hist.newest_with_prefix("te"), Some("test"));
+
+        tmp.close().unwrap();
+    }
+}
@@ -0,0 +1,394 @@
+use {
+    crate::{fuzzy::fuzzy_match, text_manipulation, History},
+    crossterm::event::{KeyCode, KeyModifiers},
+    nu_ansi_term::Style,
+    std::{cmp::Reverse, collections::HashSet},
+    unicode_width::UnicodeWidthChar,
+};
+
+/// The default number of rows the history search panel takes below the prompt
+pub static DEFAULT_SEARCH_PANEL_MAX_ROWS: u16 = 10;
+
+/// How the search term of the [`HistorySearchPanel`] is matched against the history entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistorySearchMode {
+    /// The characters of the term appear in the entry in order, the best matches are listed
+    /// first. The case is ignored unless the term contains uppercase characters
+    Fuzzy,
+    /// The entry contains the term, the most recent entries are listed first
+    Substring,
+    /// The entry starts with the term, the most recent entries are listed first
+    Prefix,
+}
+
+impl HistorySearchMode {
+    fn next(self) -> HistorySearchMode {
+        match self {
+            HistorySearchMode::Fuzzy => HistorySearchMode::Substring,
+            HistorySearchMode::Substring => HistorySearchMode::Prefix,
+            HistorySearchMode::Prefix => HistorySearchMode::Fuzzy,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            HistorySearchMode::Fuzzy => "fuzzy",
+            HistorySearchMode::Substring => "substring",
+            HistorySearchMode::Prefix => "prefix",
+        }
+    }
+}
+
+/// What the engine does after the panel handled a key
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum SearchPanelEvent {
+    /// The search or the selection changed
+    Changed,
+    /// Submit the selected entry
+    Accept,
+    /// Put the selected entry into the buffer for editing
+    Edit,
+    /// Close the panel and keep the buffer
+    Cancel,
+    /// The key isn't used by the panel
+    Ignored,
+}
+
+#[derive(Debug, Clone)]
+struct HistoryMatch {
+    entry: String,
+    // Indices of the matched characters of the entry
+    positions: Vec<usize>,
+}
+
+/// An interactive search through the history, listing the best matches below the prompt
+///
+/// Typing edits the search term, Up and Down select a match, Enter submits the selected entry
+/// and Tab puts it into the buffer for editing. Ctrl-R switches between fuzzy, substring and
+/// prefix matching, Esc closes the panel. The characters matching the term are highlighted.
+/// Entries that occur several times in the history are listed once.
+///
+/// # Example
+/// ```rust,no_run
+/// use reedline::{HistorySearchMode, HistorySearchPanel, Reedline};
+///
+/// let mut line_editor = Reedline::new().with_history_search_panel(
+///     HistorySearchPanel::new(HistorySearchMode::Fuzzy).with_max_rows(8),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct HistorySearchPanel {
+    mode: HistorySearchMode,
+    term: String,
+    matches: Vec<HistoryMatch>,
+    total_matches: usize,
+    selected: usize,
+    max_rows: u16,
+    // Layout for the last known screen size
+    screen_width: usize,
+    visible_matches: usize,
+}
+
+impl Default for HistorySearchPanel {
+    fn default() -> Self {
+        HistorySearchPanel::new(HistorySearchMode::Fuzzy)
+    }
+}
+
+impl HistorySearchPanel {
+    /// Create a panel that starts searching in the given mode
+    pub fn new(mode: HistorySearchMode) -> HistorySearchPanel {
+        HistorySearchPanel {
+            mode,
+            term: String::new(),
+            matches: vec![],
+            total_matches: 0,
+            selected: 0,
+            max_rows: DEFAULT_SEARCH_PANEL_MAX_ROWS,
+            screen_width: 0,
+            visible_matches: 0,
+        }
+    }
+
+    /// A builder that limits the rows the panel takes, including its status row
+    pub fn with_max_rows(mut self, max_rows: u16) -> HistorySearchPanel {
+        self.max_rows = max_rows.max(2);
+        self
+    }
+
+    /// The current search term
+    pub fn term(&self) -> &str {
+        &self.term
+    }
+
+    /// The current search mode
+    pub fn mode(&self) -> HistorySearchMode {
+        self.mode
+    }
+
+    /// The selected entry, if anything matches
+    pub fn selected_entry(&self) -> Option<&str> {
+        self.matches
+            .get(self.selected)
+            .map(|history_match| history_match.entry.as_str())
+    }
+
+    /// Whether the search term doesn't match any entry
+    pub fn is_failing(&self) -> bool {
+        !self.term.is_empty() && self.matches.is_empty()
+    }
+
+    /// Start a new search, listing the most recent entries
+    pub(crate) fn open(&mut self, history: &dyn History) {
+        self.term.clear();
+        self.update(history);
+    }
+
+    /// Edit the search according to the key
+    pub(crate) fn handle_key(
+        &mut self,
+        modifiers: KeyModifiers,
+        code: KeyCode,
+        history: &dyn History,
+    ) -> SearchPanelEvent {
+        match (modifiers, code) {
+            (KeyModifiers::NONE, KeyCode::Char(c)) | (KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+                self.term.push(c);
+                self.update(history);
+            }
+            (KeyModifiers::NONE, KeyCode::Backspace) => {
+                let length = text_manipulation::remove_last_grapheme(&self.term).len();
+                self.term.truncate(length);
+                self.update(history);
+            }
+            (KeyModifiers::CONTROL, KeyCode::Char('r')) => {
+                self.mode = self.mode.next();
+                self.update(history);
+            }
+            (KeyModifiers::NONE, KeyCode::Up) | (KeyModifiers::CONTROL, KeyCode::Char('p')) => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            (KeyModifiers::NONE, KeyCode::Down) | (KeyModifiers::CONTROL, KeyCode::Char('n')) => {
+                if self.selected + 1 < self.matches.len() {
+                    self.selected += 1;
+                }
+            }
+            (KeyModifiers::NONE, KeyCode::Enter) => return SearchPanelEvent::Accept,
+            (KeyModifiers::NONE, KeyCode::Tab) => return SearchPanelEvent::Edit,
+            (KeyModifiers::NONE, KeyCode::Esc)
+            | (KeyModifiers::CONTROL, KeyCode::Char('g'))
+            | (KeyModifiers::CONTROL, KeyCode::Char('c')) => return SearchPanelEvent::Cancel,
+            _ => return SearchPanelEvent::Ignored,
+        }
+
+        SearchPanelEvent::Changed
+    }
+
+    /// Arrange the matches for the screen width and the rows available below the prompt.
+    ///
+    /// Returns the number of rows the panel will take when painted.
+    pub fn layout(&mut self, screen_width: u16, available_rows: u16) -> u16 {
+        self.screen_width = usize::from(screen_width).max(1);
+        let max_rows = usize::from(self.max_rows.min(available_rows)).max(1);
+        // Keep a row for the status
+        self.visible_matches = self.matches.len().min(max_rows - 1);
+
+        (self.visible_matches + 1) as u16
+    }
+
+    /// The rows of the panel ready to be painted, the matches followed by a status row
+    pub fn lines(&self) -> Vec<String> {
+        let first = (self.selected + 1).saturating_sub(self.visible_matches);
+
+        let mut lines: Vec<String> = self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(self.visible_matches)
+            .map(|(index, history_match)| self.line(history_match, index == self.selected))
+            .collect();
+
+        lines.push(
+            Style::new()
+                .dimmed()
+                .paint(format!(
+                    "-- {} search, {} of {} --",
+                    self.mode.name(),
+                    self.matches.len(),
+                    self.total_matches
+                ))
+                .to_string(),
+        );
+
+        lines
+    }
+
+    // Search the history for the term, newest entries first
+    fn update(&mut self, history: &dyn History) {
+        let top = usize::from(self.max_rows).saturating_sub(1);
+        let ignore_case = !self.term.chars().any(char::is_uppercase);
+        let mut seen = HashSet::new();
+        let entries = history
+            .iter_chronologic()
+            .rev()
+            .filter(|entry| seen.insert(entry.as_str()));
+
+        self.matches = match self.mode {
+            HistorySearchMode::Fuzzy => {
+                let mut scored: Vec<(i32, usize, &str, Vec<usize>)> = entries
+                    .enumerate()
+                    .filter_map(|(recency, entry)| {
+                        let (score, positions) = fuzzy_match(&self.term, entry, ignore_case)?;
+                        Some((score, recency, entry.as_str(), positions))
+                    })
+                    .collect();
+                self.total_matches = scored.len();
+                // The best matches first, the more recent one of equally good matches
+                scored.sort_by_key(|(score, recency, _, _)| (Reverse(*score), *recency));
+                scored
+                    .into_iter()
+                    .take(top)
+                    .map(|(_, _, entry, positions)| HistoryMatch {
+                        entry: entry.to_string(),
+                        positions,
+                    })
+                    .collect()
+            }
+            HistorySearchMode::Substring | HistorySearchMode::Prefix => {
+                let term = &self.term;
+                let prefix = self.mode == HistorySearchMode::Prefix;
+                let found: Vec<(&String, usize)> = entries
+                    .filter_map(|entry| match entry.find(term.as_str()) {
+                        Some(0) => Some((entry, 0)),
+                        Some(start) if !prefix => Some((entry, start)),
+                        _ => None,
+                    })
+                    .collect();
+                self.total_matches = found.len();
+                found
+                    .into_iter()
+                    .take(top)
+                    .map(|(entry, start)| {
+                        let first = entry[..start].chars().count();
+                        HistoryMatch {
+                            entry: entry.clone(),
+                            positions: (first..first + term.chars().count()).collect(),
+                        }
+                    })
+                    .collect()
+            }
+        };
+        self.selected = 0;
+    }
+
+    // A match cut to the screen width with the matched characters highlighted, the selected
+    // match is shown reversed
+    fn line(&self, history_match: &HistoryMatch, selected: bool) -> String {
+        let style = if selected {
+            Style::new().reverse()
+        } else {
+            Style::new()
+        };
+
+        let mut line = String::new();
+        let mut width = 0;
+        for (index, c) in history_match.entry.chars().enumerate() {
+            // Multiline entries are shown on a single row
+            let c = if c == '\n' { '↵' } else { c };
+            width += c.width().unwrap_or(0);
+            if width > self.screen_width.saturating_sub(1) {
+                break;
+            }
+
+            let style = if history_match.positions.contains(&index) {
+                style.bold().underline()
+            } else {
+                style
+            };
+            line.push_str(&style.paint(c.to_string()).to_string());
+        }
+
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileBackedHistory;
+    use pretty_assertions::assert_eq;
+
+    fn history(entries: &[&str]) -> Box<dyn History> {
+        let mut history: Box<dyn History> = Box::new(FileBackedHistory::default());
+        for entry in entries {
+            history.append(entry.to_string());
+        }
+        history
+    }
+
+    fn search(panel: &mut HistorySearchPanel, history: &dyn History, term: &str) -> Vec<String> {
+        panel.open(history);
+        for c in term.chars() {
+            panel.handle_key(KeyModifiers::NONE, KeyCode::Char(c), history);
+        }
+        panel
+            .matches
+            .iter()
+            .map(|history_match| history_match.entry.clone())
+            .collect()
+    }
+
+    #[test]
+    fn fuzzy_search_lists_the_best_matches_first() {
+        let history = history(&["git checkout main", "gecko", "cargo", "git commit", "gecko"]);
+        let mut panel = HistorySearchPanel::new(HistorySearchMode::Fuzzy);
+
+        assert_eq!(
+            search(&mut panel, history.as_ref(), "gco"),
+            vec!["git commit", "git checkout main", "gecko"]
+        );
+        assert_eq!(panel.matches[0].positions, vec![0, 4, 5]);
+        assert_eq!(panel.total_matches, 3);
+    }
+
+    #[test]
+    fn substring_and_prefix_search_list_recent_entries_first() {
+        let history = history(&["cargo test", "git status", "cargo build", "ls"]);
+        let mut panel = HistorySearchPanel::new(HistorySearchMode::Substring);
+
+        assert_eq!(
+            search(&mut panel, history.as_ref(), "t"),
+            vec!["git status", "cargo test"]
+        );
+        assert_eq!(panel.matches[1].positions, vec![6]);
+
+        panel.handle_key(KeyModifiers::CONTROL, KeyCode::Char('r'), history.as_ref());
+        assert_eq!(panel.mode(), HistorySearchMode::Prefix);
+        assert_eq!(search(&mut panel, history.as_ref(), "car").len(), 2);
+        assert_eq!(
+            search(&mut panel, history.as_ref(), "t"),
+            Vec::<String>::new()
+        );
+        assert!(panel.is_failing());
+    }
+
+    #[test]
+    fn selection_moves_within_the_listed_matches() {
+        let history = history(&["one", "two", "three"]);
+        let mut panel = HistorySearchPanel::new(HistorySearchMode::Substring).with_max_rows(3);
+        panel.open(history.as_ref());
+        panel.layout(40, 10);
+
+        assert_eq!(panel.selected_entry(), Some("three"));
+        for _ in 0..3 {
+            panel.handle_key(KeyModifiers::NONE, KeyCode::Down, history.as_ref());
+        }
+        assert_eq!(panel.selected_entry(), Some("two"));
+        assert_eq!(panel.lines().len(), 3);
+        assert_eq!(
+            panel.handle_key(KeyModifiers::NONE, KeyCode::Tab, history.as_ref()),
+            SearchPanelEvent::Edit
+        );
+    }
+}
@@ -27,12 +27,15 @@ impl Keybindings {
         Self { bindings: vec![] }
     }
 
+    /// Bind a key combination to a list of edit commands, replacing any existing binding for it
     pub fn add_binding(
         &mut self,
         modifier: KeyModifiers,
         key_code: KeyCode,
         edit_commands: Vec<EditCommand>,
     ) {
+        self.bindings
+            .retain(|binding| !(binding.modifier == modifier && binding.key_code == key_code));
         self.bindings.push(Keybinding {
             modifier,
             key_code,
@@ -55,6 +58,7 @@ impl Keybindings {
     }
 }
 
+/// Returns the current default keybindings of vi normal mode
 pub fn default_vi_normal_keybindings() -> Keybindings {
     use KeyCode::*;
 
@@ -84,6 +88,7 @@ pub fn default_vi_normal_keybindings() -> Keybindings {
     keybindings
 }
 
+/// Returns the current default keybindings of vi insert mode
 pub fn default_vi_insert_keybindings() -> Keybindings {
     use KeyCode::*;
 
@@ -93,7 +98,11 @@ pub fn default_vi_insert_keybindings() -> Keybindings {
     keybindings.add_binding(KeyModifiers::NONE, Up, vec![EditCommand::PreviousHistory]);
     keybindings.add_binding(KeyModifiers::NONE, Down, vec![EditCommand::NextHistory]);
     keybindings.add_binding(KeyModifiers::NONE, Left, vec![EditCommand::MoveLeft]);
-    keybindings.add_binding(KeyModifiers::NONE, Right, vec![EditCommand::MoveRight]);
+    keybindings.add_binding(
+        KeyModifiers::NONE,
+        Right,
+        vec![EditCommand::AcceptHint, EditCommand::MoveRight],
+    );
     keybindings.add_binding(KeyModifiers::NONE, Backspace, vec![EditCommand::Backspace]);
     keybindings.add_binding(KeyModifiers::NONE, Delete, vec![EditCommand::Delete]);
 
@@ -177,6 +186,11 @@ pub fn default_emacs_keybindings() -> Keybindings {
         Char('r'),
         vec![EditCommand::SearchHistory],
     );
+    keybindings.add_binding(
+        KeyModifiers::CONTROL,
+        Char('s'),
+        vec![EditCommand::SearchHistoryForward],
+    );
     keybindings.add_binding(
         KeyModifiers::CONTROL,
         Char('t'),
@@ -195,7 +209,7 @@ pub fn default_emacs_keybindings() -> Keybindings {
     keybindings.add_binding(
         KeyModifiers::ALT,
         Char('f'),
-        vec![EditCommand::MoveWordRight],
+        vec![EditCommand::AcceptHintWord, EditCommand::MoveWordRight],
     );
     keybindings.add_binding(
         KeyModifiers::ALT,
@@ -234,11 +248,43 @@ pub fn default_emacs_keybindings() -> Keybindings {
     keybindings.add_binding(KeyModifiers::NONE, Backspace, vec![EditCommand::Backspace]);
     keybindings.add_binding(KeyModifiers::NONE, Delete, vec![EditCommand::Delete]);
     keybindings.add_binding(KeyModifiers::NONE, Home, vec![EditCommand::MoveToStart]);
-    keybindings.add_binding(KeyModifiers::NONE, End, vec![EditCommand::MoveToEnd]);
+    // At the end of the buffer the hint is accepted, else the cursor moves as usual
+    keybindings.add_binding(
+        KeyModifiers::NONE,
+        End,
+        vec![EditCommand::AcceptHint, EditCommand::MoveToEnd],
+    );
     keybindings.add_binding(KeyModifiers::NONE, Up, vec![EditCommand::Up]);
     keybindings.add_binding(KeyModifiers::NONE, Down, vec![EditCommand::Down]);
     keybindings.add_binding(KeyModifiers::NONE, Left, vec![EditCommand::MoveLeft]);
-    keybindings.add_binding(KeyModifiers::NONE, Right, vec![EditCommand::MoveRight]);
+    keybindings.add_binding(
+        KeyModifiers::NONE,
+        Right,
+        vec![EditCommand::AcceptHint, EditCommand::MoveRight],
+    );
 
     keybindings
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adding_a_binding_replaces_the_existing_one() {
+        let mut keybindings = default_emacs_keybindings();
+        let bindings_count = keybindings.bindings.len();
+
+        keybindings.add_binding(
+            KeyModifiers::CONTROL,
+            KeyCode::Char('a'),
+            vec![EditCommand::MoveToEnd],
+        );
+
+        assert_eq!(keybindings.bindings.len(), bindings_count);
+        assert_eq!(
+            keybindings.find_binding(KeyModifiers::CONTROL, KeyCode::Char('a')),
+            Some(vec![EditCommand::MoveToEnd])
+        );
+    }
+}
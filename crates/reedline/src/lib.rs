@@ -146,6 +146,17 @@
 //! );
 //! ```
 //!
+//! ## Integrate with custom Validator
+//!
+//! ```rust
+//! // Create a reedline object that continues editing on a new line
+//! // while the input is incomplete
+//!
+//! use reedline::{DefaultValidator, Reedline};
+//!
+//! let mut line_editor = Reedline::new().with_validator(Box::new(DefaultValidator));
+//! ```
+//!
 //! ## Are we prompt yet? (Development status)
 //!
 //! This crate is currently under active development
@@ -181,7 +192,7 @@ mod clip_buffer;
 mod text_manipulation;
 
 mod enums;
-pub use enums::{EditCommand, EditMode, Signal};
+pub use enums::{EditCommand, EditMode, HistoryNavigationPolicy, Signal};
 
 mod painter;
 
@@ -189,7 +200,9 @@ mod engine;
 pub use engine::Reedline;
 
 mod history;
-pub use history::{FileBackedHistory, History, HISTORY_SIZE};
+#[cfg(feature = "sqlite")]
+pub use history::SqliteBackedHistory;
+pub use history::{FileBackedHistory, History, HistoryMetadata, HISTORY_SIZE};
 
 mod prompt;
 pub use prompt::{
@@ -200,7 +213,9 @@ pub use prompt::{
 mod line_buffer;
 
 mod keybindings;
-pub use keybindings::default_emacs_keybindings;
+pub use keybindings::{
+    default_emacs_keybindings, default_vi_insert_keybindings, default_vi_normal_keybindings,
+};
 
 mod syntax_highlighting_fileio;
 
@@ -215,8 +230,34 @@ pub use styled_text::StyledText;
 
 mod completer;
 pub use completer::{
-    ComplationActionHandler, Completer, DefaultCompleter, DefaultCompletionActionHandler, Span,
+    ComplationActionHandler, Completer, CompleterAdapter, DefaultCompleter,
+    DefaultCompletionActionHandler, Span, Suggestion, SuggestionCompleter, SuggestionKind,
+};
+
+mod completer_chain;
+pub use completer_chain::{CompleterChain, CompletionSource};
+
+mod fuzzy;
+
+mod filename_completer;
+pub use filename_completer::FilenameCompleter;
+
+mod background_completer;
+pub use background_completer::{BackgroundCompleter, DEFAULT_COMPLETION_BUDGET};
+
+mod completion_menu;
+pub use completion_menu::{CompletionMenu, MenuCompletionActionHandler, DEFAULT_MENU_MAX_ROWS};
+
+mod history_search_panel;
+pub use history_search_panel::{
+    HistorySearchMode, HistorySearchPanel, DEFAULT_SEARCH_PANEL_MAX_ROWS,
 };
 
 mod hinter;
 pub use hinter::{DefaultHinter, Hinter};
+
+mod terminal;
+pub use terminal::{CrosstermTerminal, Terminal, VirtualTerminal};
+
+mod validator;
+pub use validator::{DefaultValidator, ValidationResult, Validator};
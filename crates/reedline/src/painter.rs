@@ -2,20 +2,20 @@ use {
     crate::{
         hinter::Hinter,
         prompt::{PromptEditMode, PromptHistorySearch},
-        Highlighter, History, Prompt,
+        CompletionMenu, Highlighter, History, Prompt, Terminal,
     },
     crossterm::{
-        cursor::{self, position, MoveTo, MoveToColumn, RestorePosition, SavePosition},
+        cursor::{self, MoveTo, MoveToColumn, RestorePosition, SavePosition},
         style::{Color, Print, ResetColor, SetForegroundColor},
-        terminal::{self, Clear, ClearType},
+        terminal::{Clear, ClearType},
         QueueableCommand, Result,
     },
-    std::io::{Stdout, Write},
+    std::io::Write,
 };
 
 pub struct Painter {
-    // Stdout
-    stdout: Stdout,
+    // Terminal output and input events
+    terminal: Box<dyn Terminal>,
 
     // Buffer Highlighter
     buffer_highlighter: Box<dyn Highlighter>,
@@ -25,23 +25,32 @@ pub struct Painter {
 
 impl Painter {
     pub fn new(
-        stdout: Stdout,
+        terminal: Box<dyn Terminal>,
         buffer_highlighter: Box<dyn Highlighter>,
         hinter: Box<dyn Hinter>,
     ) -> Self {
         Painter {
-            stdout,
+            terminal,
             buffer_highlighter,
             hinter,
         }
     }
 
     pub fn queue_move_to(&mut self, column: u16, row: u16) -> Result<()> {
-        self.stdout.queue(cursor::MoveTo(column, row))?;
+        self.terminal.queue(cursor::MoveTo(column, row))?;
 
         Ok(())
     }
 
+    pub fn set_terminal(&mut self, terminal: Box<dyn Terminal>) {
+        self.terminal = terminal;
+    }
+
+    /// The terminal that is painted to, also used to read input events
+    pub fn terminal(&mut self) -> &mut dyn Terminal {
+        self.terminal.as_mut()
+    }
+
     pub fn set_highlighter(&mut self, buffer_highlighter: Box<dyn Highlighter>) {
         self.buffer_highlighter = buffer_highlighter;
     }
@@ -50,6 +59,16 @@ impl Painter {
         self.hinter = hinter;
     }
 
+    /// The hint painted after the cursor, without styling
+    pub fn current_hint(&self) -> String {
+        self.hinter.current_hint()
+    }
+
+    /// Whether the hinter is still computing the hint of the last painted buffer
+    pub fn is_hint_pending(&self) -> bool {
+        self.hinter.is_pending()
+    }
+
     /// Queue the complete prompt to display including status indicators (e.g. pwd, time)
     ///
     /// Used at the beginning of each [`Reedline::read_line()`] call.
@@ -62,7 +81,7 @@ impl Painter {
         let (screen_width, _) = terminal_size;
 
         // print our prompt
-        self.stdout
+        self.terminal
             .queue(MoveToColumn(0))?
             .queue(SetForegroundColor(prompt.get_prompt_color()))?
             .queue(Print(prompt.render_prompt(screen_width as usize)))?
@@ -82,7 +101,7 @@ impl Painter {
         prompt_mode: PromptEditMode,
     ) -> Result<()> {
         // print our prompt
-        self.stdout
+        self.terminal
             .queue(MoveToColumn(0))?
             .queue(SetForegroundColor(prompt.get_prompt_color()))?
             .queue(Print(prompt.render_prompt_indicator(prompt_mode)))?
@@ -94,12 +113,16 @@ impl Painter {
     /// Repaint logic for the normal input prompt buffer
     ///
     /// Requires coordinates where the input buffer begins after the prompt.
+    /// Continuation lines of a multiline buffer start with the prompt's multiline indicator.
+    /// The completion menu, if any, is painted in the rows below the buffer.
     pub fn queue_buffer(
         &mut self,
+        prompt: &dyn Prompt,
         original_line: String,
         prompt_offset: (u16, u16),
         cursor_position_in_buffer: usize,
         history: &dyn History,
+        menu: Option<&CompletionMenu>,
     ) -> Result<()> {
         let highlighted_line = self
             .buffer_highlighter
@@ -108,16 +131,23 @@ impl Painter {
 
         let (before_cursor, after_cursor) = highlighted_line;
 
-        let before_cursor_lines = before_cursor.lines();
-        let after_cursor_lines = after_cursor.lines();
+        let before_cursor_lines = before_cursor.split('\n');
+        let after_cursor_lines = after_cursor.split('\n');
+
+        let prompt_color = prompt.get_prompt_color();
+        let multiline_indicator = prompt.render_prompt_multiline_indicator();
 
         let mut commands = self
-            .stdout
+            .terminal
             .queue(MoveTo(prompt_offset.0, prompt_offset.1))?;
 
         for (idx, before_cursor_line) in before_cursor_lines.enumerate() {
             if idx != 0 {
-                commands = commands.queue(Print("\r\n"))?;
+                commands = commands
+                    .queue(Print("\r\n"))?
+                    .queue(SetForegroundColor(prompt_color))?
+                    .queue(Print(&multiline_indicator))?
+                    .queue(ResetColor)?;
             }
             commands = commands.queue(Print(before_cursor_line))?;
         }
@@ -132,15 +162,24 @@ impl Painter {
 
         for (idx, after_cursor_line) in after_cursor_lines.enumerate() {
             if idx != 0 {
-                commands = commands.queue(Print("\r\n"))?;
+                commands = commands
+                    .queue(Print("\r\n"))?
+                    .queue(SetForegroundColor(prompt_color))?
+                    .queue(Print(&multiline_indicator))?
+                    .queue(ResetColor)?;
             }
             commands = commands.queue(Print(after_cursor_line))?;
         }
 
-        commands
-            .queue(Clear(ClearType::FromCursorDown))?
-            .queue(RestorePosition)?
-            .flush()?;
+        commands = commands.queue(Clear(ClearType::FromCursorDown))?;
+
+        if let Some(menu) = menu {
+            for menu_line in menu.lines() {
+                commands = commands.queue(Print("\r\n"))?.queue(Print(menu_line))?;
+            }
+        }
+
+        commands.queue(RestorePosition)?.flush()?;
 
         Ok(())
     }
@@ -155,16 +194,24 @@ impl Painter {
         buffer: String,
         terminal_size: (u16, u16),
         history: &dyn History,
+        menu: Option<&CompletionMenu>,
     ) -> Result<(u16, u16)> {
-        self.stdout.queue(cursor::Hide)?;
+        self.terminal.queue(cursor::Hide)?;
         self.queue_move_to(prompt_origin.0, prompt_origin.1)?;
         self.queue_prompt(prompt, prompt_mode, terminal_size)?;
-        self.stdout.queue(cursor::Show)?;
+        self.terminal.queue(cursor::Show)?;
         self.flush()?;
         // set where the input begins
-        let prompt_offset = position()?;
-        self.queue_buffer(buffer, prompt_offset, cursor_position_in_buffer, history)?;
-        self.stdout.queue(cursor::Show)?;
+        let prompt_offset = self.terminal.position()?;
+        self.queue_buffer(
+            prompt,
+            buffer,
+            prompt_offset,
+            cursor_position_in_buffer,
+            history,
+            menu,
+        )?;
+        self.terminal.queue(cursor::Show)?;
         self.flush()?;
 
         Ok(prompt_offset)
@@ -176,7 +223,7 @@ impl Painter {
         prompt_search: PromptHistorySearch,
     ) -> Result<()> {
         // print search prompt
-        self.stdout
+        self.terminal
             .queue(MoveToColumn(0))?
             .queue(SetForegroundColor(Color::Blue))?
             .queue(Print(
@@ -187,12 +234,43 @@ impl Painter {
         Ok(())
     }
 
+    /// Queue the search indicator on the prompt row and the rows of the search panel below it
+    pub fn queue_history_search_panel(
+        &mut self,
+        prompt: &dyn Prompt,
+        prompt_search: PromptHistorySearch,
+        prompt_row: u16,
+        lines: &[String],
+    ) -> Result<()> {
+        let mut commands = self
+            .terminal
+            .queue(MoveTo(0, prompt_row))?
+            .queue(SetForegroundColor(Color::Blue))?
+            .queue(Print(
+                prompt.render_prompt_history_search_indicator(prompt_search),
+            ))?
+            .queue(ResetColor)?
+            .queue(SavePosition)?
+            .queue(Clear(ClearType::UntilNewLine))?;
+
+        for line in lines {
+            commands = commands.queue(Print("\r\n"))?.queue(Print(line))?;
+        }
+
+        commands
+            .queue(Clear(ClearType::FromCursorDown))?
+            .queue(RestorePosition)?
+            .flush()?;
+
+        Ok(())
+    }
+
     pub fn queue_history_search_result(
         &mut self,
         history_result: &str,
         offset: usize,
     ) -> Result<()> {
-        self.stdout
+        self.terminal
             .queue(Print(&history_result[..offset]))?
             .queue(SavePosition)?
             .queue(Print(&history_result[offset..]))?
@@ -204,11 +282,11 @@ impl Painter {
 
     /// Writes `line` to the terminal with a following carriage return and newline
     pub fn paint_line(&mut self, line: &str) -> Result<()> {
-        self.stdout
+        self.terminal
             .queue(Print(line))?
             .queue(Print("\n"))?
             .queue(MoveToColumn(1))?;
-        self.stdout.flush()?;
+        self.terminal.flush()?;
 
         Ok(())
     }
@@ -217,38 +295,46 @@ impl Painter {
     ///
     /// Also works in raw mode
     pub fn paint_crlf(&mut self) -> Result<()> {
-        self.stdout.queue(Print("\n"))?.queue(MoveToColumn(1))?;
-        self.stdout.flush()?;
+        self.terminal.queue(Print("\n"))?.queue(MoveToColumn(1))?;
+        self.terminal.flush()?;
 
         Ok(())
     }
 
     // Printing carriage return
     pub fn paint_carriage_return(&mut self) -> Result<()> {
-        self.stdout.queue(Print("\r\n\r\n"))?.flush()
+        self.terminal.queue(Print("\r\n\r\n"))?.flush()
+    }
+
+    /// Scroll the screen content up by `rows` by moving below the last row
+    pub fn scroll_up(&mut self, screen_height: u16, rows: u16) -> Result<()> {
+        self.terminal
+            .queue(MoveTo(0, screen_height.saturating_sub(1)))?
+            .queue(Print("\n".repeat(usize::from(rows))))?
+            .flush()
     }
 
     /// Clear the screen by printing enough whitespace to start the prompt or
     /// other output back at the first line of the terminal.
     pub fn clear_screen(&mut self) -> Result<()> {
-        let (_, num_lines) = terminal::size()?;
+        let (_, num_lines) = self.terminal.size()?;
         for _ in 0..2 * num_lines {
-            self.stdout.queue(Print("\n"))?;
+            self.terminal.queue(Print("\n"))?;
         }
-        self.stdout.queue(MoveTo(0, 0))?;
-        self.stdout.flush()?;
+        self.terminal.queue(MoveTo(0, 0))?;
+        self.terminal.flush()?;
 
         Ok(())
     }
 
     pub fn clear_until_newline(&mut self) -> Result<()> {
-        self.stdout.queue(Clear(ClearType::UntilNewLine))?;
-        self.stdout.flush()?;
+        self.terminal.queue(Clear(ClearType::UntilNewLine))?;
+        self.terminal.flush()?;
 
         Ok(())
     }
 
     pub fn flush(&mut self) -> Result<()> {
-        self.stdout.flush()
+        self.terminal.flush()
     }
 }
@@ -0,0 +1,31 @@
+use {
+    crossterm::{event::Event, Result},
+    std::{io::Write, time::Duration},
+};
+
+/// The terminal [`crate::Reedline`] reads its input events from and paints its output to.
+///
+/// Output is written through the [`Write`] implementation, so any crossterm command can be
+/// queued on it. The remaining methods replace the global crossterm functions for input,
+/// raw mode and size/cursor queries, which allows running the engine without a TTY.
+pub trait Terminal: Write {
+    /// Wait up to `timeout` for an input event, returns `true` if one is available
+    fn poll(&mut self, timeout: Duration) -> Result<bool>;
+
+    /// Read the next input event, blocking until one is available
+    fn read(&mut self) -> Result<Event>;
+
+    /// The size of the terminal as `(columns, rows)`
+    fn size(&self) -> Result<(u16, u16)>;
+
+    /// The current position of the cursor as `(column, row)`
+    ///
+    /// Queued output has to be flushed before for the position to be accurate.
+    fn position(&mut self) -> Result<(u16, u16)>;
+
+    /// Switch the terminal into raw mode for the duration of a [`crate::Reedline::read_line()`] call
+    fn enable_raw_mode(&mut self) -> Result<()>;
+
+    /// Restore the terminal from raw mode
+    fn disable_raw_mode(&mut self) -> Result<()>;
+}
@@ -0,0 +1,62 @@
+use {
+    super::Terminal,
+    crossterm::{cursor, event, event::Event, terminal, Result},
+    std::{
+        io::{stdout, Stdout, Write},
+        time::Duration,
+    },
+};
+
+/// The [`Terminal`] backed by the process' stdout and crossterm's global event reader
+pub struct CrosstermTerminal {
+    stdout: Stdout,
+}
+
+impl Default for CrosstermTerminal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CrosstermTerminal {
+    /// Construct a terminal writing to stdout
+    pub fn new() -> Self {
+        CrosstermTerminal { stdout: stdout() }
+    }
+}
+
+impl Write for CrosstermTerminal {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.stdout.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stdout.flush()
+    }
+}
+
+impl Terminal for CrosstermTerminal {
+    fn poll(&mut self, timeout: Duration) -> Result<bool> {
+        event::poll(timeout)
+    }
+
+    fn read(&mut self) -> Result<Event> {
+        event::read()
+    }
+
+    fn size(&self) -> Result<(u16, u16)> {
+        terminal::size()
+    }
+
+    fn position(&mut self) -> Result<(u16, u16)> {
+        cursor::position()
+    }
+
+    fn enable_raw_mode(&mut self) -> Result<()> {
+        terminal::enable_raw_mode()
+    }
+
+    fn disable_raw_mode(&mut self) -> Result<()> {
+        terminal::disable_raw_mode()
+    }
+}
@@ -0,0 +1,7 @@
+mod base;
+mod crossterm_terminal;
+mod virtual_terminal;
+
+pub use base::Terminal;
+pub use crossterm_terminal::CrosstermTerminal;
+pub use virtual_terminal::VirtualTerminal;
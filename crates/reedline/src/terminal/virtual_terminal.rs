@@ -0,0 +1,519 @@
+use {
+    super::Terminal,
+    crossterm::{
+        event::{Event, KeyCode, KeyEvent, KeyModifiers},
+        Result,
+    },
+    std::{
+        cell::RefCell,
+        collections::VecDeque,
+        io::{self, Write},
+        rc::Rc,
+        time::Duration,
+    },
+    unicode_width::UnicodeWidthChar,
+};
+
+/// In-memory [`Terminal`] that replays scripted input events and renders the output to a
+/// screen grid, so the engine can be driven without a TTY.
+///
+/// Clones share the same state, keep one around to inspect the screen after handing a
+/// clone to [`crate::Reedline::with_terminal()`].
+///
+/// Understands the subset of ANSI escape sequences emitted by crossterm for cursor movement,
+/// clearing and saving/restoring the cursor. Styling is accepted but not recorded.
+/// Once all scripted events are consumed [`Terminal::poll()`] returns an
+/// [`io::ErrorKind::UnexpectedEof`] error instead of blocking forever.
+///
+/// # Example
+/// ```
+/// use {
+///     crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers},
+///     reedline::{DefaultPrompt, Reedline, Signal, VirtualTerminal},
+/// };
+///
+/// let terminal = VirtualTerminal::new(80, 24);
+/// terminal.type_str("hello");
+/// terminal.push_event(Event::Key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)));
+///
+/// let mut line_editor = Reedline::new().with_terminal(Box::new(terminal.clone()));
+/// let sig = line_editor.read_line(&DefaultPrompt::default()).unwrap();
+///
+/// assert!(matches!(sig, Signal::Success(buffer) if buffer == "hello"));
+/// assert!(terminal.screen().iter().any(|row| row.ends_with("hello")));
+/// ```
+#[derive(Clone)]
+pub struct VirtualTerminal {
+    state: Rc<RefCell<VirtualTerminalState>>,
+}
+
+struct VirtualTerminalState {
+    events: VecDeque<Event>,
+    width: u16,
+    height: u16,
+    // `None` marks the right half of a double width character
+    grid: Vec<Vec<Option<char>>>,
+    // The column may equal `width` when a wrap is pending after writing the last column
+    cursor: (u16, u16),
+    saved_cursor: (u16, u16),
+    raw_mode: bool,
+    // Bytes of an incomplete UTF-8 sequence from the previous write
+    incomplete: Vec<u8>,
+    // Contents of an escape sequence after the ESC that is not yet terminated
+    escape: Option<String>,
+}
+
+impl VirtualTerminal {
+    /// Construct an empty terminal with `width` columns and `height` rows
+    pub fn new(width: u16, height: u16) -> Self {
+        assert!(
+            width > 0 && height > 0,
+            "VirtualTerminal needs at least one row and column"
+        );
+        VirtualTerminal {
+            state: Rc::new(RefCell::new(VirtualTerminalState {
+                events: VecDeque::new(),
+                width,
+                height,
+                grid: vec![blank_row(width); height as usize],
+                cursor: (0, 0),
+                saved_cursor: (0, 0),
+                raw_mode: false,
+                incomplete: vec![],
+                escape: None,
+            })),
+        }
+    }
+
+    /// Queue an input event to be returned by [`Terminal::read()`]
+    pub fn push_event(&self, event: Event) {
+        self.state.borrow_mut().events.push_back(event);
+    }
+
+    /// Queue a key press without modifiers
+    pub fn push_key(&self, code: KeyCode) {
+        self.push_event(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+    }
+
+    /// Queue a key press for every character of `text`
+    pub fn type_str(&self, text: &str) {
+        for c in text.chars() {
+            self.push_key(KeyCode::Char(c));
+        }
+    }
+
+    /// Number of scripted events that have not been read yet
+    pub fn pending_events(&self) -> usize {
+        self.state.borrow().events.len()
+    }
+
+    /// The rendered rows of the screen with trailing whitespace removed
+    pub fn screen(&self) -> Vec<String> {
+        self.state
+            .borrow()
+            .grid
+            .iter()
+            .map(|row| {
+                let line: String = row.iter().flatten().collect();
+                line.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    /// The current cursor position as `(column, row)`
+    pub fn cursor(&self) -> (u16, u16) {
+        self.state.borrow().cursor_position()
+    }
+
+    /// Whether the terminal is currently in raw mode
+    pub fn is_raw_mode(&self) -> bool {
+        self.state.borrow().raw_mode
+    }
+
+    /// Change the size of the screen and queue the corresponding resize event
+    ///
+    /// The screen keeps at least one row and column, smaller sizes are clamped.
+    pub fn resize(&self, width: u16, height: u16) {
+        let (width, height) = (width.max(1), height.max(1));
+        {
+            let mut state = self.state.borrow_mut();
+            state.width = width;
+            state.height = height;
+            for row in state.grid.iter_mut() {
+                row.resize(width as usize, Some(' '));
+            }
+            state.grid.resize(height as usize, blank_row(width));
+            state.cursor.0 = state.cursor.0.min(width - 1);
+            state.cursor.1 = state.cursor.1.min(height - 1);
+        }
+        self.push_event(Event::Resize(width, height));
+    }
+}
+
+impl Write for VirtualTerminal {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.state.borrow_mut().process(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Terminal for VirtualTerminal {
+    fn poll(&mut self, _timeout: Duration) -> Result<bool> {
+        if self.state.borrow().events.is_empty() {
+            Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "VirtualTerminal ran out of scripted events",
+            ))
+        } else {
+            Ok(true)
+        }
+    }
+
+    fn read(&mut self) -> Result<Event> {
+        self.state.borrow_mut().events.pop_front().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "VirtualTerminal ran out of scripted events",
+            )
+        })
+    }
+
+    fn size(&self) -> Result<(u16, u16)> {
+        let state = self.state.borrow();
+        Ok((state.width, state.height))
+    }
+
+    fn position(&mut self) -> Result<(u16, u16)> {
+        Ok(self.cursor())
+    }
+
+    fn enable_raw_mode(&mut self) -> Result<()> {
+        self.state.borrow_mut().raw_mode = true;
+        Ok(())
+    }
+
+    fn disable_raw_mode(&mut self) -> Result<()> {
+        self.state.borrow_mut().raw_mode = false;
+        Ok(())
+    }
+}
+
+fn blank_row(width: u16) -> Vec<Option<char>> {
+    vec![Some(' '); width as usize]
+}
+
+impl VirtualTerminalState {
+    fn cursor_position(&self) -> (u16, u16) {
+        (self.cursor.0.min(self.width - 1), self.cursor.1)
+    }
+
+    fn process(&mut self, buf: &[u8]) {
+        let mut bytes = std::mem::take(&mut self.incomplete);
+        bytes.extend_from_slice(buf);
+
+        let (text, rest) = match std::str::from_utf8(&bytes) {
+            Ok(text) => (text.to_string(), vec![]),
+            Err(err) => {
+                let valid = err.valid_up_to();
+                let text = String::from_utf8_lossy(&bytes[..valid]).into_owned();
+                match err.error_len() {
+                    // Sequence got split between two writes
+                    None => (text, bytes[valid..].to_vec()),
+                    Some(_) => (String::from_utf8_lossy(&bytes).into_owned(), vec![]),
+                }
+            }
+        };
+        self.incomplete = rest;
+
+        for c in text.chars() {
+            self.process_char(c);
+        }
+    }
+
+    fn process_char(&mut self, c: char) {
+        if let Some(mut sequence) = self.escape.take() {
+            match (sequence.is_empty(), c) {
+                (true, '[') => {
+                    sequence.push(c);
+                    self.escape = Some(sequence);
+                }
+                (true, '7') => self.saved_cursor = self.cursor,
+                (true, '8') => self.cursor = self.saved_cursor,
+                // Other two character escapes are not supported
+                (true, _) => {}
+                (false, '\u{40}'..='\u{7e}') => self.csi(&sequence[1..], c),
+                (false, _) => {
+                    sequence.push(c);
+                    self.escape = Some(sequence);
+                }
+            }
+            return;
+        }
+
+        match c {
+            '\x1b' => self.escape = Some(String::new()),
+            '\r' => self.cursor.0 = 0,
+            '\n' => {
+                // Outside of raw mode the tty translates a newline into a carriage return and newline
+                if !self.raw_mode {
+                    self.cursor.0 = 0;
+                }
+                self.line_feed();
+            }
+            '\t' => self.cursor.0 = ((self.cursor.0 / 8 + 1) * 8).min(self.width - 1),
+            '\x08' => self.cursor.0 = self.cursor_position().0.saturating_sub(1),
+            c if c.is_control() => {}
+            c => self.print(c),
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        let char_width = c.width().unwrap_or(0) as u16;
+        // A double width character doesn't fit on a single column screen
+        if char_width == 0 || char_width > self.width {
+            return;
+        }
+        if self.cursor.0 + char_width > self.width {
+            self.cursor.0 = 0;
+            self.line_feed();
+        }
+        let (column, row) = (self.cursor.0 as usize, self.cursor.1 as usize);
+        self.grid[row][column] = Some(c);
+        if char_width == 2 {
+            self.grid[row][column + 1] = None;
+        }
+        self.cursor.0 += char_width;
+    }
+
+    fn line_feed(&mut self) {
+        if self.cursor.1 + 1 < self.height {
+            self.cursor.1 += 1;
+        } else {
+            // Scroll the screen contents up by one line
+            self.grid.remove(0);
+            self.grid.push(blank_row(self.width));
+        }
+    }
+
+    fn csi(&mut self, parameters: &str, command: char) {
+        // Private modes (e.g. cursor visibility) don't affect the screen contents
+        if parameters.starts_with('?') {
+            return;
+        }
+        let parameters: Vec<u16> = parameters
+            .split(';')
+            .map(|p| p.parse().unwrap_or(0))
+            .collect();
+        let nth = |n: usize| parameters.get(n).copied().unwrap_or(0);
+        let count = nth(0).max(1);
+        let (column, row) = self.cursor_position();
+
+        match command {
+            'H' | 'f' => {
+                self.cursor = (
+                    (nth(1).max(1) - 1).min(self.width - 1),
+                    (nth(0).max(1) - 1).min(self.height - 1),
+                )
+            }
+            'G' => self.cursor.0 = (count - 1).min(self.width - 1),
+            'd' => self.cursor.1 = (count - 1).min(self.height - 1),
+            'A' => self.cursor = (column, row.saturating_sub(count)),
+            'B' => self.cursor = (column, (row + count).min(self.height - 1)),
+            'C' => self.cursor = ((column + count).min(self.width - 1), row),
+            'D' => self.cursor = (column.saturating_sub(count), row),
+            'E' => self.cursor = (0, (row + count).min(self.height - 1)),
+            'F' => self.cursor = (0, row.saturating_sub(count)),
+            'J' => match nth(0) {
+                0 => {
+                    self.clear_line_range(row, column as usize..);
+                    for row in (row + 1)..self.height {
+                        self.clear_line_range(row, ..);
+                    }
+                }
+                1 => {
+                    for row in 0..row {
+                        self.clear_line_range(row, ..);
+                    }
+                    self.clear_line_range(row, ..=column as usize);
+                }
+                _ => {
+                    for row in 0..self.height {
+                        self.clear_line_range(row, ..);
+                    }
+                }
+            },
+            'K' => match nth(0) {
+                0 => self.clear_line_range(row, column as usize..),
+                1 => self.clear_line_range(row, ..=column as usize),
+                _ => self.clear_line_range(row, ..),
+            },
+            // Styling, scrolling regions etc. are ignored
+            _ => {}
+        }
+    }
+
+    fn clear_line_range<R>(&mut self, row: u16, range: R)
+    where
+        R: std::slice::SliceIndex<[Option<char>], Output = [Option<char>]>,
+    {
+        for cell in self.grid[row as usize][range].iter_mut() {
+            *cell = Some(' ');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crossterm::{
+            cursor::{MoveTo, MoveToColumn, RestorePosition, SavePosition},
+            style::{Color, Print, ResetColor, SetForegroundColor},
+            terminal::{Clear, ClearType},
+            QueueableCommand,
+        },
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn prints_text_and_moves_cursor() {
+        let mut terminal = VirtualTerminal::new(10, 3);
+        terminal.write_all(b"hello").unwrap();
+
+        assert_eq!(terminal.screen(), vec!["hello", "", ""]);
+        assert_eq!(terminal.cursor(), (5, 0));
+    }
+
+    #[test]
+    fn wraps_at_the_end_of_the_line() {
+        let mut terminal = VirtualTerminal::new(4, 3);
+        terminal.write_all(b"abcd").unwrap();
+        assert_eq!(terminal.cursor(), (3, 0));
+
+        terminal.write_all(b"ef").unwrap();
+        assert_eq!(terminal.screen(), vec!["abcd", "ef", ""]);
+        assert_eq!(terminal.cursor(), (2, 1));
+    }
+
+    #[test]
+    fn scrolls_when_writing_past_the_last_row() {
+        let mut terminal = VirtualTerminal::new(10, 2);
+        terminal.write_all(b"one\r\ntwo\r\nthree").unwrap();
+
+        assert_eq!(terminal.screen(), vec!["two", "three"]);
+        assert_eq!(terminal.cursor(), (5, 1));
+    }
+
+    #[test]
+    fn newline_returns_carriage_only_outside_of_raw_mode() {
+        let mut terminal = VirtualTerminal::new(10, 3);
+        terminal.write_all(b"ab\ncd").unwrap();
+        terminal.enable_raw_mode().unwrap();
+        terminal.write_all(b"\nef").unwrap();
+
+        assert_eq!(terminal.screen(), vec!["ab", "cd", "  ef"]);
+    }
+
+    #[test]
+    fn interprets_crossterm_commands() {
+        let mut terminal = VirtualTerminal::new(10, 3);
+        terminal
+            .queue(Print("0123456789"))
+            .unwrap()
+            .queue(MoveTo(2, 1))
+            .unwrap()
+            .queue(SetForegroundColor(Color::Red))
+            .unwrap()
+            .queue(Print("ab"))
+            .unwrap()
+            .queue(ResetColor)
+            .unwrap()
+            .queue(SavePosition)
+            .unwrap()
+            .queue(MoveTo(4, 0))
+            .unwrap()
+            .queue(Clear(ClearType::UntilNewLine))
+            .unwrap()
+            .queue(RestorePosition)
+            .unwrap()
+            .queue(MoveToColumn(0))
+            .unwrap()
+            .queue(Print("x"))
+            .unwrap();
+        terminal.flush().unwrap();
+
+        assert_eq!(terminal.screen(), vec!["0123", "x ab", ""]);
+        assert_eq!(terminal.cursor(), (1, 1));
+    }
+
+    #[test]
+    fn clears_from_cursor_down() {
+        let mut terminal = VirtualTerminal::new(5, 3);
+        terminal.write_all(b"aaaaabbbbbccccc").unwrap();
+        terminal
+            .queue(MoveTo(2, 1))
+            .unwrap()
+            .queue(Clear(ClearType::FromCursorDown))
+            .unwrap();
+
+        assert_eq!(terminal.screen(), vec!["aaaaa", "bb", ""]);
+    }
+
+    #[test]
+    fn double_width_characters_take_two_cells() {
+        let mut terminal = VirtualTerminal::new(10, 1);
+        terminal.write_all("〉ab".as_bytes()).unwrap();
+
+        assert_eq!(terminal.screen(), vec!["〉ab"]);
+        assert_eq!(terminal.cursor(), (4, 0));
+    }
+
+    #[test]
+    fn handles_utf8_split_across_writes() {
+        let mut terminal = VirtualTerminal::new(10, 1);
+        let bytes = "é".as_bytes();
+        terminal.write_all(&bytes[..1]).unwrap();
+        terminal.write_all(&bytes[1..]).unwrap();
+
+        assert_eq!(terminal.screen(), vec!["é"]);
+    }
+
+    #[test]
+    fn replays_scripted_events_and_errors_when_exhausted() {
+        let mut terminal = VirtualTerminal::new(10, 1);
+        terminal.type_str("a");
+
+        assert!(terminal.poll(Duration::from_secs(1)).unwrap());
+        assert_eq!(
+            terminal.read().unwrap(),
+            Event::Key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            terminal.poll(Duration::from_secs(1)).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn resizing_keeps_at_least_one_cell() {
+        let mut terminal = VirtualTerminal::new(10, 3);
+        terminal.write_all(b"hello").unwrap();
+        terminal.resize(0, 0);
+
+        assert_eq!(terminal.size().unwrap(), (1, 1));
+        assert_eq!(terminal.cursor(), (0, 0));
+        assert_eq!(terminal.read().unwrap(), Event::Resize(1, 1));
+    }
+
+    #[test]
+    fn double_width_characters_are_skipped_on_a_single_column() {
+        let mut terminal = VirtualTerminal::new(1, 2);
+        terminal.write_all("〉a".as_bytes()).unwrap();
+
+        assert_eq!(terminal.screen(), vec!["a", ""]);
+    }
+}
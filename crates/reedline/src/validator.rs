@@ -0,0 +1,49 @@
+/// The validation trait. Implementers of this trait decide if the current buffer is a complete
+/// input or if pressing `Enter` should start a new line to continue editing it
+pub trait Validator {
+    /// The action that will handle the current buffer as a line and return whether it is complete
+    fn validate(&self, line: &str) -> ValidationResult;
+}
+
+/// Whether or not the validation shows the input was complete
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationResult {
+    /// An incomplete input which needs more lines to be complete
+    Incomplete,
+
+    /// An input that is complete as-is
+    Complete,
+}
+
+/// A simple, example validator that considers input with unbalanced brackets incomplete
+///
+/// # Example
+/// ```
+/// use reedline::{DefaultValidator, ValidationResult, Validator};
+///
+/// let validator = DefaultValidator;
+///
+/// assert_eq!(validator.validate("ls | each {"), ValidationResult::Incomplete);
+/// assert_eq!(validator.validate("ls | each { echo $it }"), ValidationResult::Complete);
+/// ```
+#[derive(Default)]
+pub struct DefaultValidator;
+
+impl Validator for DefaultValidator {
+    fn validate(&self, line: &str) -> ValidationResult {
+        let mut depth: i64 = 0;
+        for c in line.chars() {
+            match c {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                _ => {}
+            }
+        }
+
+        if depth > 0 {
+            ValidationResult::Incomplete
+        } else {
+            ValidationResult::Complete
+        }
+    }
+}